@@ -0,0 +1,430 @@
+// si_trace_print_macros/src/lib.rs
+
+//! Attribute macros for [`si_trace_print`].
+//!
+//! `#[si_trace]` prints the enter and exit of a function, like a matching
+//! `defn!` and `defx!`, without cluttering the function.
+//!
+//! ```rust
+//! use si_trace_print_macros::si_trace;
+//!
+//! #[si_trace]
+//! fn func1(var: usize, name: &str) -> Result<usize, String> {
+//!     if var == 0 {
+//!         return Err(String::from("zero"));
+//!     }
+//!     let parsed: usize = name.parse::<usize>().map_err(|err| err.to_string())?;
+//!     Ok(var + parsed)
+//! }
+//!
+//! fn main() {
+//!     _ = func1(3, "4");
+//!     _ = func1(3, "four");
+//! }
+//! ```
+//!
+//! prints to *stderr*
+//!
+//! ```text
+//! →func1: (3, "4")
+//! ←func1: Ok(7)
+//! →func1: (3, "four")
+//! ←func1: Err("invalid digit found in string")
+//! ```
+//!
+//! `#[si_trace_impl]` prints the enter and exit of every method of an `impl`
+//! block, named like `Counter::add`.
+//!
+//! [`si_trace_print`]: https://docs.rs/si_trace_print
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{
+    parse_macro_input, parse_quote, Block, Expr, FnArg, Ident, ImplItem, ImplItemFn, Item, ItemFn, ItemImpl, LitStr,
+    Meta, Pat, ReturnType, Signature, Stmt, Type,
+};
+
+/// Options of `#[si_trace(...)]`.
+#[derive(Default)]
+struct Options {
+    /// Arguments not printed in the enter line.
+    skip: Vec<Ident>,
+    /// Do not print the returned value in the exit line.
+    skip_return: bool,
+    /// The function name printed instead of [`function_name!`].
+    ///
+    /// [`function_name!`]: https://docs.rs/si_trace_print/latest/si_trace_print/function_name/macro.function_name.html
+    name: Option<LitStr>,
+    /// Print to stdout instead of stderr.
+    stdout: bool,
+}
+
+impl Options {
+    /// Parse one option of `#[si_trace(...)]`.
+    fn parse_meta(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("skip") {
+            meta.parse_nested_meta(|meta| {
+                self.skip.push(meta.path.require_ident()?.clone());
+                Ok(())
+            })
+        } else if meta.path.is_ident("skip_return") {
+            self.skip_return = true;
+            Ok(())
+        } else if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("stdout") {
+            self.stdout = true;
+            Ok(())
+        } else {
+            Err(meta.error("expected `skip(..)`, `skip_return`, `name = \"..\"` or `stdout`"))
+        }
+    }
+}
+
+/// Print the enter and exit of a function, indented to stack depth.
+///
+/// The enter line prints the [`Debug`] value of each argument, e.g.
+/// `→func1: (3, "four")`.
+/// The exit line prints the [`Debug`] returned value, e.g. `←func1: Ok(7)`,
+/// for each `return` and for the returned tail expression.
+/// For each `?` that returns early, the exit line prints the early return
+/// before conversion, e.g. `←func1: Err(ParseIntError { kind: InvalidDigit })`
+/// or `←func1: None`.
+/// If the function panics then the exit line is `←func1: [panicking]`.
+///
+/// Options:
+/// - `skip(arg1, arg2)`: do not print these arguments, e.g. arguments that
+///   are not [`Debug`]. `self` and arguments that are patterns are never
+///   printed.
+/// - `skip_return`: do not print the returned value, e.g. a returned value
+///   that is not [`Debug`].
+/// - `name = "Foo::bar"`: print this function name instead of
+///   `function_name!()`.
+/// - `stdout`: print to *stdout* like `dpfn!` instead of *stderr* like
+///   `defn!`.
+///
+/// ```rust
+/// use si_trace_print_macros::si_trace;
+///
+/// #[si_trace(skip(callback), skip_return)]
+/// fn func2(var: usize, callback: fn(usize)) -> fn(usize) {
+///     callback(var);
+///     callback
+/// }
+/// ```
+///
+/// Like the `de*` macros, only prints in debug builds, uses `sn()` and
+/// `sx()` indentation, and may be disabled by `SI_TRACE` or filtered by
+/// `set_filter`.
+///
+/// Returned values, and the early returns of `?` for [`Result`] errors, must
+/// be [`Debug`] unless `skip_return`. The `?` operator must be used on a
+/// [`Result`] or [`Option`] unless `skip_return`.
+/// Not printed are `return` and `?` within closures, `async` blocks, and
+/// macros, e.g. `println!("{}", func()?)`; for these the exit line prints
+/// no returned value.
+///
+/// A `const fn` cannot print.
+#[proc_macro_attribute]
+pub fn si_trace(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = Options::default();
+    let parser = syn::meta::parser(|meta| options.parse_meta(meta));
+    parse_macro_input!(attr with parser);
+    let mut function = parse_macro_input!(item as ItemFn);
+    match instrument(&function.sig, &mut function.block, &options) {
+        Ok(()) => quote!(#function).into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Options of `#[si_trace_impl(...)]`.
+#[derive(Default)]
+struct ImplOptions {
+    /// Methods not printed.
+    skip: Vec<Ident>,
+    /// Do not print methods that return a field of `self`.
+    skip_getters: bool,
+    /// Print to stdout instead of stderr.
+    stdout: bool,
+}
+
+impl ImplOptions {
+    /// Parse one option of `#[si_trace_impl(...)]`.
+    fn parse_meta(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("skip") {
+            meta.parse_nested_meta(|meta| {
+                self.skip.push(meta.path.require_ident()?.clone());
+                Ok(())
+            })
+        } else if meta.path.is_ident("skip_getters") {
+            self.skip_getters = true;
+            Ok(())
+        } else if meta.path.is_ident("stdout") {
+            self.stdout = true;
+            Ok(())
+        } else {
+            Err(meta.error("expected `skip(..)`, `skip_getters` or `stdout`"))
+        }
+    }
+}
+
+/// Print the enter and exit of every method of an `impl` block, like
+/// [`macro@si_trace`], named `Type::method`, or `<Type as Trait>::method` for
+/// a trait implementation.
+///
+/// Options:
+/// - `skip(method1, method2)`: do not print these methods.
+/// - `skip_getters`: do not print methods that only return a field of
+///   `self`, e.g. `fn count(&self) -> usize { self.count }`.
+/// - `stdout`: print to *stdout* instead of *stderr*.
+///
+/// A method may have its own `#[si_trace(...)]` attribute for
+/// [`macro@si_trace`] options, e.g. `skip_return`. A method with its own
+/// attribute is printed even if skipped by `skip` or `skip_getters`.
+/// A `const fn` is never printed.
+///
+/// ```rust
+/// use si_trace_print_macros::si_trace_impl;
+///
+/// struct Counter {
+///     count: usize,
+/// }
+///
+/// #[si_trace_impl(skip_getters)]
+/// impl Counter {
+///     fn add(&mut self, by: usize) -> usize {
+///         self.count += by;
+///         self.count
+///     }
+///     fn count(&self) -> usize {
+///         self.count
+///     }
+/// }
+/// ```
+///
+/// calling `counter.add(2)` prints
+///
+/// ```text
+/// →Counter::add: (2)
+/// ←Counter::add: 3
+/// ```
+#[proc_macro_attribute]
+pub fn si_trace_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = ImplOptions::default();
+    let parser = syn::meta::parser(|meta| options.parse_meta(meta));
+    parse_macro_input!(attr with parser);
+    let mut impl_ = parse_macro_input!(item as ItemImpl);
+    match instrument_impl(&mut impl_, &options) {
+        Ok(()) => quote!(#impl_).into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// [`instrument`] each method of `impl_`.
+fn instrument_impl(impl_: &mut ItemImpl, options: &ImplOptions) -> syn::Result<()> {
+    let self_name: String = type_name(&impl_.self_ty);
+    let prefix: String = match &impl_.trait_ {
+        Some((_, trait_, _)) => match trait_.segments.last() {
+            Some(segment) => format!("<{} as {}>", self_name, segment.ident),
+            None => self_name,
+        },
+        None => self_name,
+    };
+    for item in impl_.items.iter_mut() {
+        let method: &mut ImplItemFn = match item {
+            ImplItem::Fn(method) => method,
+            _ => continue,
+        };
+        let mut fn_options = Options {
+            stdout: options.stdout,
+            ..Options::default()
+        };
+        // a method's own `#[si_trace(...)]`
+        let mut traced: bool = false;
+        let mut attrs = Vec::with_capacity(method.attrs.len());
+        for attr in method.attrs.drain(..) {
+            match attr.path().segments.last() {
+                Some(segment) if segment.ident == "si_trace" => {
+                    traced = true;
+                    if let Meta::List(_) = attr.meta {
+                        attr.parse_nested_meta(|meta| fn_options.parse_meta(meta))?;
+                    }
+                }
+                _ => attrs.push(attr),
+            }
+        }
+        method.attrs = attrs;
+        if method.sig.constness.is_some() {
+            continue;
+        }
+        if !traced && (options.skip.contains(&method.sig.ident) || (options.skip_getters && is_getter(method))) {
+            continue;
+        }
+        if fn_options.name.is_none() {
+            let name: String = format!("{}::{}", prefix, method.sig.ident);
+            fn_options.name = Some(LitStr::new(&name, method.sig.ident.span()));
+        }
+        instrument(&method.sig, &mut method.block, &fn_options)?;
+    }
+
+    Ok(())
+}
+
+/// Return the name of the type of an `impl` block, e.g. `Counter` for
+/// `Counter<T>` or `mod1::Counter`.
+fn type_name(type_: &Type) -> String {
+    match type_ {
+        Type::Path(path) => match path.path.segments.last() {
+            Some(segment) => segment.ident.to_string(),
+            None => String::new(),
+        },
+        Type::Reference(reference) => type_name(&reference.elem),
+        Type::Paren(paren) => type_name(&paren.elem),
+        Type::Group(group) => type_name(&group.elem),
+        _ => quote!(#type_).to_string().replace(' ', ""),
+    }
+}
+
+/// Does `method` only return a field of `self`, e.g. `self.count` or
+/// `&self.name`?
+fn is_getter(method: &ImplItemFn) -> bool {
+    if method.sig.inputs.len() != 1 || !matches!(method.sig.inputs.first(), Some(FnArg::Receiver(_))) {
+        return false;
+    }
+    let mut expr: &Expr = match method.block.stmts.as_slice() {
+        [Stmt::Expr(expr, None)] => expr,
+        _ => return false,
+    };
+    if let Expr::Reference(reference) = expr {
+        expr = &reference.expr;
+    }
+    match expr {
+        Expr::Field(field) => matches!(&*field.base, Expr::Path(path) if path.path.is_ident("self")),
+        _ => false,
+    }
+}
+
+/// Replace the `block` of the function with signature `sig` with a block
+/// that prints the enter and exit of the function.
+fn instrument(sig: &Signature, block: &mut Block, options: &Options) -> syn::Result<()> {
+    if let Some(constness) = sig.constness {
+        return Err(syn::Error::new(
+            constness.span(),
+            "`si_trace` cannot print from a `const fn`",
+        ));
+    }
+    let guard = Ident::new("__si_trace_guard", Span::mixed_site());
+    let returned = Ident::new("__si_trace_return", Span::mixed_site());
+    let stream = if options.stdout { quote!(Stdout) } else { quote!(Stderr) };
+    let name: TokenStream2 = match &options.name {
+        Some(name) => quote!(#name),
+        None => quote!(::si_trace_print::function_name::function_name!()),
+    };
+    let args: Vec<&Ident> = sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) if pat.subpat.is_none() && !options.skip.contains(&pat.ident) => Some(&pat.ident),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect();
+    let enter: TokenStream2 = if args.is_empty() {
+        quote! {
+            ::si_trace_print::__si_print_enter_debug!(#stream, Enter, Some(#name),)
+        }
+    } else {
+        let format: String = format!("({})", vec!["{:?}"; args.len()].join(", "));
+        quote! {
+            ::si_trace_print::__si_print_enter_debug!(#stream, Enter, Some(#name), #format, #(#args),*)
+        }
+    };
+    let output: Option<&Type> = match &sig.output {
+        ReturnType::Type(_, output) if !options.skip_return && !matches!(**output, Type::Never(_)) => Some(output),
+        _ => None,
+    };
+    match output {
+        // the exit line is printed when the guard is dropped; the guard is
+        // prepended so a tail expression is not within extra braces
+        None => block.stmts.insert(0, parse_quote!(let #guard = #enter;)),
+        Some(output) => {
+            Returns { guard: &guard }.visit_block_mut(block);
+            // an `impl Trait` cannot be the type of a `let`
+            let mut impl_trait = ImplTrait(false);
+            impl_trait.visit_type(output);
+            let output: TokenStream2 = if impl_trait.0 {
+                TokenStream2::new()
+            } else {
+                quote!(: #output)
+            };
+            let value = Ident::new("__si_trace_value", Span::mixed_site());
+            // the exit line is printed within the `let`, as older compilers
+            // ignore `allow(unreachable_code)` on a statement after a block
+            // that always returns
+            *block = parse_quote! {{
+                let mut #guard = #enter;
+                #[allow(unreachable_code, clippy::diverging_sub_expression)]
+                let #returned #output = match #block { #value => #guard.__return(#value) };
+                #returned
+            }};
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the returned value of each `return` and `?` of a function
+/// with the `ExitGuard` named `guard`.
+struct Returns<'a> {
+    guard: &'a Ident,
+}
+
+impl VisitMut for Returns<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        // closures and `async` blocks return from themselves
+        if matches!(expr, Expr::Closure(_) | Expr::Async(_)) {
+            return;
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+        let guard = self.guard;
+        // `match` evaluates `value`, which may also use the guard, before
+        // borrowing the guard, and keeps the temporaries of `value`
+        let bound = Ident::new("__si_trace_value", Span::mixed_site());
+        match expr {
+            Expr::Return(return_) => {
+                if let Some(value) = return_.expr.take() {
+                    return_.expr = Some(parse_quote! {
+                        match #value { #bound => #guard.__return(#bound) }
+                    });
+                }
+            }
+            Expr::Try(try_) => {
+                let value = &try_.expr;
+                try_.expr = parse_quote! {
+                    match #value { #bound => #guard.__branch(#bound) }
+                };
+            }
+            _ => {}
+        }
+    }
+
+    // nested items return from themselves
+    fn visit_item_mut(&mut self, _item: &mut Item) {}
+}
+
+/// Does a type contain an `impl Trait`?
+struct ImplTrait(bool);
+
+impl Visit<'_> for ImplTrait {
+    fn visit_type_impl_trait(&mut self, _impl_trait: &syn::TypeImplTrait) {
+        self.0 = true;
+    }
+}
@@ -0,0 +1,201 @@
+// si_trace_print_macros/tests/si_trace.rs
+
+// the instrumented functions must not cause warnings, e.g. `unused_braces`
+#![deny(warnings)]
+
+use si_trace_print::defo;
+use si_trace_print::printers::capture;
+use si_trace_print_macros::si_trace;
+
+use std::thread;
+
+/// Return everything printed by `f` called from a new thread.
+fn capture_thread(f: fn()) -> String {
+    thread::spawn(move || capture(f)).join().unwrap()
+}
+
+#[si_trace]
+fn add(a: usize, b: &str) -> usize {
+    defo!("adding");
+    a + b.len()
+}
+
+#[test]
+fn test_si_trace() {
+    fn call() {
+        add(1, "two");
+    }
+    assert_eq!(capture_thread(call), "→add: (1, \"two\")\n add: adding\n←add: 4\n");
+}
+
+#[si_trace]
+fn nothing() {
+    defo!("nothing");
+}
+
+#[test]
+fn test_si_trace_unit() {
+    assert_eq!(capture_thread(nothing), "→nothing: \n nothing: nothing\n←nothing: \n");
+}
+
+// `unused_braces` is only for a block on one line
+#[rustfmt::skip]
+#[si_trace]
+fn tail(number: u8) { nothing_with(number) }
+
+fn nothing_with(_number: u8) {}
+
+#[rustfmt::skip]
+#[si_trace(skip_return)]
+fn tail_skip_return(number: u8) -> u8 { number }
+
+#[rustfmt::skip]
+#[si_trace]
+fn tail_never(message: &str) -> ! { panic!("{}", message) }
+
+#[test]
+fn test_si_trace_unit_tail() {
+    fn call() {
+        tail(1);
+        assert_eq!(tail_skip_return(2), 2);
+    }
+    assert_eq!(
+        capture_thread(call),
+        "→tail: (1)\n←tail: \n→tail_skip_return: (2)\n←tail_skip_return: \n"
+    );
+    fn call_never() {
+        assert!(std::panic::catch_unwind(|| tail_never("oops")).is_err());
+    }
+    assert_eq!(
+        capture_thread(call_never),
+        "→tail_never: (\"oops\")\n←tail_never: [panicking]\n"
+    );
+}
+
+#[si_trace]
+fn parse(number: &str) -> Result<u32, String> {
+    if number.is_empty() {
+        return Err(String::from("empty"));
+    }
+    let parsed: u32 = number.parse::<u32>().map_err(|err| err.to_string())?;
+    Ok(parsed + 1)
+}
+
+#[test]
+fn test_si_trace_return() {
+    fn call() {
+        _ = parse("1");
+        _ = parse("");
+        _ = parse("one");
+    }
+    assert_eq!(
+        capture_thread(call),
+        "\
+→parse: (\"1\")
+←parse: Ok(2)
+→parse: (\"\")
+←parse: Err(\"empty\")
+→parse: (\"one\")
+←parse: Err(\"invalid digit found in string\")
+"
+    );
+}
+
+#[si_trace]
+fn first_even(numbers: &[u32]) -> Option<u32> {
+    let even: u32 = *numbers.iter().find(|number| *number % 2 == 0)?;
+    // `?` within `return` within `?`
+    return Some(Some(even).filter(|even| *even > 0)? + u32::from(Some(even)? > 2));
+}
+
+#[test]
+fn test_si_trace_question() {
+    fn call() {
+        _ = first_even(&[1, 4]);
+        _ = first_even(&[1, 3]);
+        _ = first_even(&[0]);
+    }
+    assert_eq!(
+        capture_thread(call),
+        "\
+→first_even: ([1, 4])
+←first_even: Some(5)
+→first_even: ([1, 3])
+←first_even: None
+→first_even: ([0])
+←first_even: None
+"
+    );
+}
+
+struct Counter {
+    count: usize,
+}
+
+impl Counter {
+    #[si_trace]
+    fn count_mut(&mut self, by: usize) -> &mut usize {
+        self.count += by;
+        &mut self.count
+    }
+
+    #[si_trace(skip(f), skip_return, name = "Counter::map")]
+    fn map(&self, f: fn(usize) -> Counter) -> Counter {
+        f(self.count)
+    }
+}
+
+#[si_trace]
+fn evens(below: usize) -> impl Iterator<Item = usize> {
+    (0..below).filter(|number| number % 2 == 0)
+}
+
+#[test]
+fn test_si_trace_options() {
+    fn double(count: usize) -> Counter {
+        Counter { count: count * 2 }
+    }
+    fn call() {
+        let mut counter = Counter { count: 1 };
+        *counter.count_mut(2) += 1;
+        assert_eq!(counter.map(double).count, 8);
+        assert_eq!(evens(5).count(), 3);
+    }
+    assert_eq!(
+        capture_thread(call),
+        "\
+→count_mut: (2)
+←count_mut: 3
+→Counter::map: 
+←Counter::map: 
+→evens: (5)
+←evens: Filter { iter: 0..5 }
+"
+    );
+}
+
+#[si_trace(stdout)]
+fn stdout() -> bool {
+    true
+}
+
+#[test]
+fn test_si_trace_stdout() {
+    fn call() {
+        assert!(stdout());
+    }
+    assert_eq!(capture_thread(call), "→stdout: \n←stdout: true\n");
+}
+
+#[si_trace]
+fn panics(message: &str) -> usize {
+    panic!("{}", message);
+}
+
+#[test]
+fn test_si_trace_panic() {
+    fn call() {
+        assert!(std::panic::catch_unwind(|| panics("oops")).is_err());
+    }
+    assert_eq!(capture_thread(call), "→panics: (\"oops\")\n←panics: [panicking]\n");
+}
@@ -0,0 +1,137 @@
+// si_trace_print_macros/tests/si_trace_impl.rs
+
+use si_trace_print::defo;
+use si_trace_print::printers::capture;
+use si_trace_print_macros::{si_trace, si_trace_impl};
+
+use std::fmt;
+use std::thread;
+
+/// Return everything printed by `f` called from a new thread.
+fn capture_thread(f: fn()) -> String {
+    thread::spawn(move || capture(f)).join().unwrap()
+}
+
+struct Counter<T> {
+    count: usize,
+    label: T,
+}
+
+#[si_trace_impl(skip(reset), skip_getters)]
+impl<T: fmt::Debug> Counter<T> {
+    fn new(label: T) -> Self {
+        Counter { count: 0, label }
+    }
+
+    fn add(&mut self, by: usize) -> usize {
+        defo!("{:?}", self.label);
+        self.count += by;
+        self.count
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn label(&self) -> &T {
+        &self.label
+    }
+
+    #[si_trace(skip_return)]
+    fn label_mut(&mut self) -> &mut T {
+        &mut self.label
+    }
+
+    const fn zero() -> usize {
+        0
+    }
+}
+
+impl<T> fmt::Debug for Counter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Counter({})", self.count)
+    }
+}
+
+#[si_trace_impl]
+impl<T> fmt::Display for Counter<T> {
+    #[si_trace(skip(f))]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.count)
+    }
+}
+
+#[test]
+fn test_si_trace_impl() {
+    fn call() {
+        let mut counter = Counter::new("a");
+        assert_eq!(counter.add(2), 2);
+        counter.reset();
+        assert_eq!(counter.count(), 0);
+        assert_eq!(*counter.label(), "a");
+        *counter.label_mut() = "b";
+        assert_eq!(Counter::<&str>::zero(), 0);
+    }
+    assert_eq!(
+        capture_thread(call),
+        "\
+→Counter::new: (\"a\")
+←Counter::new: Counter(0)
+→Counter::add: (2)
+ add: \"a\"
+←Counter::add: 2
+→Counter::label_mut: 
+←Counter::label_mut: 
+"
+    );
+}
+
+#[test]
+fn test_si_trace_impl_trait() {
+    fn call() {
+        assert_eq!(Counter { count: 3, label: () }.to_string(), "3");
+    }
+    // called from within `std`, so indented by an unknown amount
+    let printed = capture_thread(call);
+    let lines: Vec<&str> = printed.lines().map(str::trim_start).collect();
+    assert_eq!(
+        lines,
+        ["→<Counter as Display>::fmt: ", "←<Counter as Display>::fmt: Ok(())"]
+    );
+}
+
+struct Label;
+
+#[si_trace_impl]
+impl fmt::Debug for Label {
+    #[si_trace(skip(f))]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Label")
+    }
+}
+
+#[si_trace]
+fn takes(_label: &Label) {}
+
+#[test]
+fn test_si_trace_impl_debug_argument() {
+    fn call() {
+        takes(&Label);
+    }
+    // `Label::fmt` prints while the enter line of `takes` is formatted
+    let printed = capture_thread(call);
+    let lines: Vec<&str> = printed.lines().map(str::trim_start).collect();
+    assert_eq!(
+        lines,
+        [
+            "→<Label as Debug>::fmt: ",
+            "←<Label as Debug>::fmt: Ok(())",
+            "→takes: (Label)",
+            "←takes: ",
+        ]
+    );
+}
@@ -0,0 +1,192 @@
+// src/color.rs
+
+//! ANSI colors of macro printing, set by [`set_color`].
+//!
+//! When colored, printing in [`Format::Text`] has
+//! - a distinct color for each lead, e.g. green `→` enter lines and yellow
+//!   `←` exit lines, and for the message of each line.
+//! - dimmed function names.
+//! - indentation colored by stack offset, cycling through six colors, so the
+//!   columns of [`Style::Tree`] guides or a [`set_indent_unit`] like `".   "`
+//!   are easy to follow.
+//!
+//! By default, printing is colored only if printing to a terminal and the
+//! environment variable [`NO_COLOR`] is not set.
+//!
+//! [`Format::Text`]: crate::format::Format::Text
+//! [`Style::Tree`]: crate::stack::Style::Tree
+//! [`set_indent_unit`]: crate::stack::set_indent_unit
+//! [`NO_COLOR`]: https://no-color.org/
+
+use std::sync::RwLock;
+
+extern crate lazy_static;
+use lazy_static::lazy_static;
+
+use crate::record::Kind;
+
+/// When to color macro printing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Color {
+    /// Color printing to a terminal, unless environment variable
+    /// [`ENV_NO_COLOR`] is set. Printing to a [`set_writer`] writer or a
+    /// [`capture`] is not colored. On Windows, a console is colored if it
+    /// renders or can be set to render ANSI escapes. The default.
+    ///
+    /// [`set_writer`]: crate::printers::set_writer
+    /// [`capture`]: crate::printers::capture
+    #[default]
+    Auto,
+    /// Always color, e.g. for CI logs that render ANSI colors.
+    Always,
+    /// Never color.
+    Never,
+}
+
+/// Environment variable that disables [`Color::Auto`] coloring when set to a
+/// non-empty value. See <https://no-color.org/>.
+pub const ENV_NO_COLOR: &str = "NO_COLOR";
+
+lazy_static! {
+    /// See [`set_color`].
+    static ref COLOR: RwLock<Color> = RwLock::new(Color::default());
+    /// Is [`ENV_NO_COLOR`] set?
+    static ref NO_COLOR: bool = std::env::var_os(ENV_NO_COLOR).map_or(false, |value| !value.is_empty());
+    /// Is stdout a terminal?
+    static ref STDOUT_TERMINAL: bool = is_terminal(1);
+    /// Is stderr a terminal?
+    static ref STDERR_TERMINAL: bool = is_terminal(2);
+}
+
+/// Set when to color macro printing.
+/// The default is [`Color::Auto`].
+///
+/// Returns the previous setting.
+///
+/// ```rust
+/// use si_trace_print::color::{set_color, Color};
+///
+/// set_color(Color::Always);
+/// ```
+pub fn set_color(color: Color) -> Color {
+    std::mem::replace(&mut *COLOR.write().unwrap(), color)
+}
+
+/// Is printing to the standard stream with file descriptor `fd`, `1` for
+/// stdout or `2` for stderr, colored? `redirected` returns `true` when
+/// printing to a [`set_writer`] writer or a [`capture`], which are never a
+/// terminal. It is called only if the stream is a terminal.
+///
+/// Whether the stream is a terminal is checked once per process.
+///
+/// [`set_writer`]: crate::printers::set_writer
+/// [`capture`]: crate::printers::capture
+pub(crate) fn enabled<F: FnOnce() -> bool>(fd: i32, redirected: F) -> bool {
+    match *COLOR.read().unwrap() {
+        Color::Always => true,
+        Color::Never => false,
+        Color::Auto => {
+            let terminal: bool = match fd {
+                1 => *STDOUT_TERMINAL,
+                _ => *STDERR_TERMINAL,
+            };
+            !*NO_COLOR && terminal && !redirected()
+        }
+    }
+}
+
+// `std::io::IsTerminal` is stable only since Rust 1.70, after this crate's
+// MSRV 1.66.1, so the platform functions are declared here. Each declaration
+// matches the C signature of libc or of `kernel32.dll`, which the standard
+// library already links.
+
+/// Is the standard stream with file descriptor `fd` a terminal?
+#[cfg(unix)]
+fn is_terminal(fd: i32) -> bool {
+    extern "C" {
+        // `int isatty(int fd);`
+        fn isatty(fd: i32) -> i32;
+    }
+    // SAFETY: `isatty` takes no pointers and only inspects `fd`; an invalid
+    // or closed `fd` returns `0`
+    unsafe { isatty(fd) == 1 }
+}
+
+/// Is the standard stream with file descriptor `fd` a console that renders
+/// ANSI escapes?
+///
+/// A console renders ANSI escapes only in mode
+/// `ENABLE_VIRTUAL_TERMINAL_PROCESSING`, which is not set by default in a
+/// legacy console, e.g. `cmd.exe`. If not set then it is set, which fails
+/// on Windows before Windows 10.
+#[cfg(windows)]
+fn is_terminal(fd: i32) -> bool {
+    extern "system" {
+        // `HANDLE GetStdHandle(DWORD nStdHandle);`
+        fn GetStdHandle(std_handle: u32) -> *mut std::ffi::c_void;
+        // `BOOL GetConsoleMode(HANDLE hConsoleHandle, LPDWORD lpMode);`
+        fn GetConsoleMode(console_handle: *mut std::ffi::c_void, mode: *mut u32) -> i32;
+        // `BOOL SetConsoleMode(HANDLE hConsoleHandle, DWORD dwMode);`
+        fn SetConsoleMode(console_handle: *mut std::ffi::c_void, mode: u32) -> i32;
+    }
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    // `STD_OUTPUT_HANDLE` or `STD_ERROR_HANDLE`
+    let std_handle: u32 = match fd {
+        1 => -11i32 as u32,
+        _ => -12i32 as u32,
+    };
+    // SAFETY: `GetStdHandle` takes no pointers; it returns a null or invalid
+    // handle if the process has no such stream
+    let handle = unsafe { GetStdHandle(std_handle) };
+    let mut mode: u32 = 0;
+    // SAFETY: `mode` is a valid `u32` for the duration of the call, and
+    // `GetConsoleMode` fails, returning `0`, for a null or invalid `handle`
+    // or a handle that is not a console
+    if unsafe { GetConsoleMode(handle, &mut mode) } == 0 {
+        return false;
+    }
+    if mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0 {
+        return true;
+    }
+    // SAFETY: `SetConsoleMode` takes no pointers and `handle` is a console,
+    // it fails, returning `0`, for a mode the console does not support
+    unsafe { SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0 }
+}
+
+/// Is the standard stream with file descriptor `fd` a terminal?
+/// Unknown on this platform, so no.
+#[cfg(not(any(unix, windows)))]
+fn is_terminal(_fd: i32) -> bool {
+    false
+}
+
+/// ANSI reset of colors.
+pub(crate) const RESET: &str = "\x1b[0m";
+
+/// ANSI reset then dim, for function names.
+pub(crate) const DIM: &str = "\x1b[0m\x1b[2m";
+
+/// ANSI foreground colors cycled through by stack offset or thread number;
+/// red, green, yellow, blue, magenta, cyan.
+pub(crate) const CYCLE: [&str; 6] = ["\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[34m", "\x1b[35m", "\x1b[36m"];
+
+/// Return the [`CYCLE`] color for `n`, e.g. a stack offset.
+pub(crate) const fn cycle(n: usize) -> &'static str {
+    CYCLE[n % CYCLE.len()]
+}
+
+/// Return the ANSI reset then color of the lead and message of a `kind` of
+/// macro. [`Kind::Plain`] is not colored.
+pub(crate) const fn kind(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Plain => RESET,
+        // blue
+        Kind::Offset => "\x1b[0m\x1b[34m",
+        // green
+        Kind::Enter => "\x1b[0m\x1b[32m",
+        // yellow
+        Kind::Exit => "\x1b[0m\x1b[33m",
+        // cyan
+        Kind::EnterExit => "\x1b[0m\x1b[36m",
+    }
+}
@@ -0,0 +1,347 @@
+// src/folded.rs
+
+//! Collect time spent in functions traced with enter and exit macros
+//! (e.g. `pfn!` and `pfx!`, `defn!` and `defx!`) and write
+//! [folded stacks] for [flamegraph] generation.
+//!
+//! Call [`set_enabled`] at the start of the program and [`write_folded`] at
+//! the end of the program.
+//!
+//! ```rust
+//! use si_trace_print::folded::{set_enabled, write_folded};
+//! use si_trace_print::{defn, defx};
+//!
+//! fn main() {
+//!     set_enabled(true);
+//!     defn!();
+//!     func1();
+//!     defx!();
+//!     write_folded(&mut std::io::stdout()).unwrap();
+//! }
+//!
+//! fn func1() {
+//!     defn!();
+//!     defx!();
+//! }
+//! ```
+//!
+//! prints the microseconds spent in each function, not including time spent
+//! in traced functions it called
+//!
+//! ```text
+//! mycrate::main 53
+//! mycrate::main;mycrate::func1 21
+//! ```
+//!
+//! Functions are named by their full path, so functions of the same name in
+//! different modules or types, e.g. `new`, are separate.
+//!
+//! Only functions with both an enter and exit print are counted. A function
+//! that printed enter but not exit is counted as part of its calling
+//! function. It is forgotten when another function is entered at the same
+//! or a lesser stack offset.
+//!
+//! Only printing macros are counted. A function silenced by
+//! [`printers::set_filter`], [`printers::set_enabled`] or environment
+//! variable [`SI_TRACE`] prints nothing, so it is not counted and its time is
+//! counted as part of its calling function.
+//!
+//! [`printers::set_filter`]: crate::printers::set_filter
+//! [`printers::set_enabled`]: crate::printers::set_enabled
+//! [`SI_TRACE`]: crate::printers::ENV_SI_TRACE
+//! [folded stacks]: https://github.com/brendangregg/FlameGraph#2-fold-stacks
+//! [flamegraph]: https://github.com/brendangregg/FlameGraph
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+extern crate lazy_static;
+use lazy_static::lazy_static;
+
+use crate::record::{Kind, Record};
+
+/// Is collecting enabled? See [`set_enabled`].
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// Map a folded stack, e.g. `"main;func1"`, to the time spent in the
+    /// last function of that stack.
+    static ref FOLDED: Mutex<HashMap<String, Duration>> = Mutex::new(HashMap::new());
+}
+
+/// One traced function call that has not yet exited.
+struct Frame {
+    name: &'static str,
+    stack_offset: usize,
+    start: Instant,
+    /// Time spent in traced functions called by this function.
+    children: Duration,
+}
+
+/// A thread's stack of traced function calls.
+#[derive(Default)]
+struct Frames(Vec<Frame>);
+
+impl Frames {
+    /// Function `name` was entered at `stack_offset` at `now`.
+    fn enter(&mut self, name: &'static str, stack_offset: usize, now: Instant) {
+        // drop functions at the same or a greater stack offset; they were
+        // entered but never exited
+        let at: usize = self
+            .0
+            .iter()
+            .rposition(|frame| frame.stack_offset < stack_offset)
+            .map_or(0, |at| at + 1);
+        self.0.truncate(at);
+        self.0.push(Frame {
+            name,
+            stack_offset,
+            start: now,
+            children: Duration::ZERO,
+        });
+    }
+
+    /// Function `name` was exited at `now`.
+    ///
+    /// Return the folded stack of `name` and the time spent in `name`, not
+    /// including time spent in traced functions it called.
+    /// Return `None` if `name` was not entered.
+    fn exit(&mut self, name: &'static str, now: Instant) -> Option<(String, Duration)> {
+        let at: usize = self.0.iter().rposition(|frame| frame.name == name)?;
+        // drop functions that were entered but never exited
+        self.0.truncate(at + 1);
+        let stack: String = self.0.iter().map(|frame| frame.name).collect::<Vec<&str>>().join(";");
+        let frame: Frame = self.0.pop()?;
+        let total: Duration = now.saturating_duration_since(frame.start);
+        if let Some(parent) = self.0.last_mut() {
+            parent.children += total;
+        }
+
+        Some((stack, total.saturating_sub(frame.children)))
+    }
+}
+
+thread_local! {
+    /// The current thread's traced function calls.
+    static FRAMES: RefCell<Frames> = RefCell::new(Frames::default());
+}
+
+/// Enable or disable collecting function times.
+/// Disabled by default.
+///
+/// Returns the previous setting.
+pub fn set_enabled(enabled: bool) -> bool {
+    ENABLED.swap(enabled, Ordering::SeqCst)
+}
+
+/// Collect the time of a [`Kind::Enter`] or [`Kind::Exit`] `record`.
+///
+/// Does nothing when printing from a later thread-local destructor.
+pub(crate) fn collect(record: &Record) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let now = Instant::now();
+    match record.kind {
+        Kind::Enter => {
+            _ = FRAMES.try_with(|frames| {
+                frames
+                    .borrow_mut()
+                    .enter(record.function_name_full, record.stack_offset, now)
+            });
+        }
+        Kind::Exit => {
+            let exited = FRAMES
+                .try_with(|frames| frames.borrow_mut().exit(record.function_name_full, now))
+                .ok()
+                .flatten();
+            if let Some((stack, time)) = exited {
+                *FOLDED
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .entry(stack)
+                    .or_default() += time;
+            }
+        }
+        Kind::Plain | Kind::Offset | Kind::EnterExit => {}
+    }
+}
+
+/// Write the collected folded stacks to `writer`, one line per stack,
+/// sorted by stack. Each line is the stack followed by the microseconds
+/// spent in the last function of the stack, e.g. `main;func1;func2 1234`.
+///
+/// The output may be passed to [`flamegraph.pl`] or [`inferno-flamegraph`].
+///
+/// [`flamegraph.pl`]: https://github.com/brendangregg/FlameGraph
+/// [`inferno-flamegraph`]: https://crates.io/crates/inferno
+pub fn write_folded<W: Write>(writer: &mut W) -> io::Result<()> {
+    let folded = FOLDED.lock().unwrap_or_else(|err| err.into_inner());
+    let mut stacks: Vec<(&String, &Duration)> = folded.iter().collect();
+    stacks.sort();
+    for (stack, time) in stacks {
+        writeln!(writer, "{} {}", stack, time.as_micros())?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_enabled, write_folded, Frames};
+    use crate::test_settings_lock;
+    use crate::{defn, defx};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_frames() {
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+        let mut frames = Frames::default();
+        frames.enter("main", 0, t0);
+        frames.enter("func1", 1, t0 + ms(1));
+        frames.enter("func2", 2, t0 + ms(2));
+        assert_eq!(
+            frames.exit("func2", t0 + ms(5)),
+            Some((String::from("main;func1;func2"), ms(3)))
+        );
+        assert_eq!(
+            frames.exit("func1", t0 + ms(10)),
+            Some((String::from("main;func1"), ms(6)))
+        );
+        assert_eq!(frames.exit("func3", t0 + ms(11)), None);
+        frames.enter("func4", 1, t0 + ms(12));
+        assert_eq!(
+            frames.exit("func4", t0 + ms(14)),
+            Some((String::from("main;func4"), ms(2)))
+        );
+        assert_eq!(frames.exit("main", t0 + ms(20)), Some((String::from("main"), ms(9))));
+        assert!(frames.0.is_empty());
+    }
+
+    #[test]
+    fn test_frames_missing_exit() {
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+        let mut frames = Frames::default();
+        frames.enter("main", 0, t0);
+        frames.enter("func1", 1, t0 + ms(1));
+        frames.enter("func2", 2, t0 + ms(2));
+        // `func2` never exits
+        assert_eq!(
+            frames.exit("func1", t0 + ms(4)),
+            Some((String::from("main;func1"), ms(3)))
+        );
+        assert_eq!(frames.exit("main", t0 + ms(5)), Some((String::from("main"), ms(2))));
+        assert!(frames.0.is_empty());
+    }
+
+    #[test]
+    fn test_frames_enter_without_exit() {
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+        let mut frames = Frames::default();
+        frames.enter("main", 0, t0);
+        for at in 1..100 {
+            // `func1` never exits
+            frames.enter("func1", 1, t0 + ms(at));
+        }
+        assert_eq!(frames.0.len(), 2);
+        assert_eq!(frames.exit("main", t0 + ms(100)), Some((String::from("main"), ms(100))));
+        assert!(frames.0.is_empty());
+    }
+
+    mod mod1 {
+        use crate::{defn, defx};
+
+        pub fn new() {
+            defn!();
+            defx!();
+        }
+    }
+
+    mod mod2 {
+        use crate::{defn, defx};
+
+        pub fn new() {
+            defn!();
+            super::mod1::new();
+            defx!();
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_write_folded() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defn!();
+            mod1::new();
+            mod2::new();
+            defx!();
+        }
+        set_enabled(true);
+        thread::spawn(|| crate::printers::capture(func1)).join().unwrap();
+        assert!(set_enabled(false));
+        let mut folded: Vec<u8> = Vec::new();
+        write_folded(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        let t = "si_trace_print::folded::tests";
+        let func1 = format!("{}::test_write_folded::func1", t);
+        let stacks: Vec<&str> = folded
+            .lines()
+            .filter(|line| line.starts_with(&func1))
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(
+            stacks,
+            [
+                func1.clone(),
+                format!("{};{}::mod1::new", func1, t),
+                format!("{};{}::mod2::new", func1, t),
+                format!("{};{}::mod2::new;{}::mod1::new", func1, t, t),
+            ]
+        );
+    }
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_collect_exiting() {
+        let _lock = test_settings_lock();
+        struct PrintOnDrop;
+        impl Drop for PrintOnDrop {
+            fn drop(&mut self) {
+                defn!();
+                defx!();
+            }
+        }
+        thread_local! {
+            static PRINT_ON_DROP: PrintOnDrop = const { PrintOnDrop };
+        }
+        fn func1() {
+            // thread-local destructors run in reverse order, so the frames
+            // are destroyed before `PRINT_ON_DROP` prints
+            PRINT_ON_DROP.with(|_| ());
+            defn!();
+            defx!();
+        }
+        set_enabled(true);
+        thread::spawn(func1).join().unwrap();
+        assert!(set_enabled(false));
+        let mut folded: Vec<u8> = Vec::new();
+        write_folded(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        let func1 = "si_trace_print::folded::tests::test_collect_exiting::func1 ";
+        assert_eq!(
+            folded.lines().filter(|line| line.starts_with(func1)).count(),
+            1,
+            "{:?}",
+            folded
+        );
+    }
+}
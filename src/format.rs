@@ -0,0 +1,361 @@
+// src/format.rs
+
+//! The output [`Format`] of macro printing, set by [`set_format`].
+//!
+//! The default [`Format::Text`] prints stack-indented lines, e.g.
+//!
+//! ```text
+//!     →func1: (3)
+//! ```
+//!
+//! [`Format::JsonLines`] prints one JSON object per line, e.g.
+//!
+//! ```text
+//! {"kind":"enter","depth":1,"function":"func1","function_full":"my_lib::func1","thread":"ThreadId(1)","thread_number":0,"module_path":"my_lib","file":"src/lib.rs","line":12,"message":"(3)","elapsed":null}
+//! ```
+//!
+//! [`Format::ChromeTrace`] prints [Chrome Trace Event] JSON that may be
+//! loaded into `chrome://tracing` or the [Perfetto UI], e.g.
+//!
+//! ```text
+//! [
+//! {"name":"func1","cat":"enter","ph":"B","ts":8.114,"pid":4810,"tid":0,"args":{"function_full":"my_lib::func1","file":"src/lib.rs","line":12,"message":"(3)"}},
+//! ```
+//!
+//! Each format prints to the same destination; stdout, stderr, the
+//! [`set_writer`] writer or the current [`capture`].
+//!
+//! [Chrome Trace Event]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+//! [Perfetto UI]: https://ui.perfetto.dev/
+//! [`set_writer`]: crate::printers::set_writer
+//! [`capture`]: crate::printers::capture
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
+
+extern crate lazy_static;
+use lazy_static::lazy_static;
+
+use crate::record::{Kind, Record};
+
+/// Output format of macro printing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Stack-indented text. The default.
+    #[default]
+    Text,
+    /// One JSON object per line, see [JSON Lines].
+    ///
+    /// The object members are:
+    /// - `"kind"`: one of `"plain"`, `"offset"`, `"enter"`, `"exit"`,
+    ///   `"enter_exit"`.
+    /// - `"depth"`: the stack offset.
+    /// - `"function"`: the [`function_name!`].
+    /// - `"function_full"`: the [`function_name_full!`].
+    /// - `"thread"`: the thread ID.
+    /// - `"thread_number"`: the [`Record::thread_number`].
+    /// - `"module_path"`, `"file"`, `"line"`: the macro source location.
+    /// - `"message"`: the printed message.
+    /// - `"elapsed"`: the [`Record::elapsed`] in microseconds, or `null`.
+    ///
+    /// [JSON Lines]: https://jsonlines.org/
+    /// [`function_name!`]: crate::function_name::function_name
+    /// [`function_name_full!`]: crate::function_name::function_name_full
+    JsonLines,
+    /// [Chrome Trace Event] JSON Array Format, for `chrome://tracing` or the
+    /// [Perfetto UI].
+    ///
+    /// The first event printed after [`set_format`] is preceded by line `[`.
+    /// Each event is one line followed by a `,`. The closing `]` is
+    /// optional in this format and is not printed.
+    ///
+    /// Events are:
+    /// - [`Kind::Enter`] is a duration begin `"B"` event.
+    /// - [`Kind::Exit`] is a duration end `"E"` event.
+    /// - [`Kind::EnterExit`], [`Kind::Offset`], [`Kind::Plain`] are
+    ///   thread-scoped instant `"i"` events.
+    ///
+    /// Event `"cat"` is the [`Kind::as_str`]. Event `"ts"` is microseconds
+    /// since the first `ChromeTrace` event. Event `"tid"` is a small number
+    /// assigned to each thread in order of first event.
+    ///
+    /// The `p*` macros print to stdout and the `e*` macros print to stderr
+    /// so use [`set_writer`] or only one family of macros to get one
+    /// complete trace.
+    ///
+    /// [Chrome Trace Event]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+    /// [Perfetto UI]: https://ui.perfetto.dev/
+    /// [`Kind::Enter`]: crate::record::Kind::Enter
+    /// [`Kind::Exit`]: crate::record::Kind::Exit
+    /// [`Kind::EnterExit`]: crate::record::Kind::EnterExit
+    /// [`Kind::Offset`]: crate::record::Kind::Offset
+    /// [`Kind::Plain`]: crate::record::Kind::Plain
+    /// [`Kind::as_str`]: crate::record::Kind::as_str
+    /// [`set_writer`]: crate::printers::set_writer
+    ChromeTrace,
+}
+
+lazy_static! {
+    /// The format set by [`set_format`].
+    static ref FORMAT: RwLock<Format> = RwLock::new(Format::default());
+    /// [`Format::ChromeTrace`] event timestamps are relative to this.
+    static ref CHROME_TRACE_START: Instant = Instant::now();
+    /// Map a `ThreadId` to a [`Format::ChromeTrace`] event `"tid"`.
+    static ref CHROME_TRACE_TIDS: Mutex<HashMap<ThreadId, usize>> = Mutex::new(HashMap::new());
+}
+
+/// Has the [`Format::ChromeTrace`] opening `[` been printed?
+static CHROME_TRACE_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Set the output format of all macro printing.
+///
+/// Returns the previous format.
+///
+/// ```rust
+/// use si_trace_print::format::{set_format, Format};
+/// use si_trace_print::{defn, defx};
+///
+/// set_format(Format::JsonLines);
+/// defn!("hello");
+/// defx!("goodbye");
+/// ```
+pub fn set_format(format: Format) -> Format {
+    CHROME_TRACE_STARTED.store(false, Ordering::SeqCst);
+    std::mem::replace(&mut *FORMAT.write().unwrap(), format)
+}
+
+/// Return the current output format.
+pub fn format() -> Format {
+    *FORMAT.read().unwrap()
+}
+
+/// Write `s` as the contents of a JSON string, i.e. escaped and without
+/// surrounding quotes.
+struct JsonEscape<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+impl Write for JsonEscape<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            match c {
+                '"' => self.0.write_str("\\\"")?,
+                '\\' => self.0.write_str("\\\\")?,
+                '\n' => self.0.write_str("\\n")?,
+                '\r' => self.0.write_str("\\r")?,
+                '\t' => self.0.write_str("\\t")?,
+                c if c.is_control() => write!(self.0, "\\u{:04x}", c as u32)?,
+                c => self.0.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Display a [`Record`] as one [`Format::JsonLines`] object, without the
+/// trailing newline.
+pub(crate) struct JsonLine<'a, 'b>(pub &'a Record<'b>);
+
+impl fmt::Display for JsonLine<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let record = self.0;
+        f.write_str("{\"kind\":\"")?;
+        f.write_str(record.kind.as_str())?;
+        write!(f, "\",\"depth\":{},\"function\":\"", record.stack_offset)?;
+        JsonEscape(f).write_str(record.function_name)?;
+        f.write_str("\",\"function_full\":\"")?;
+        JsonEscape(f).write_str(record.function_name_full)?;
+        f.write_str("\",\"thread\":\"")?;
+        write!(JsonEscape(f), "{:?}", record.thread_id)?;
+        write!(f, "\",\"thread_number\":{},\"module_path\":\"", record.thread_number)?;
+        JsonEscape(f).write_str(record.module_path)?;
+        f.write_str("\",\"file\":\"")?;
+        JsonEscape(f).write_str(record.file)?;
+        write!(f, "\",\"line\":{},\"message\":\"", record.line)?;
+        write!(JsonEscape(f), "{}", record.message)?;
+        match record.elapsed {
+            Some(elapsed) => write!(f, "\",\"elapsed\":{:.3}}}", elapsed.as_secs_f64() * 1_000_000.0),
+            None => f.write_str("\",\"elapsed\":null}"),
+        }
+    }
+}
+
+/// Return `"[\n"` for the first [`Format::ChromeTrace`] event printed
+/// after [`set_format`], otherwise `""`.
+pub(crate) fn chrome_trace_header() -> &'static str {
+    if CHROME_TRACE_STARTED.swap(true, Ordering::SeqCst) {
+        ""
+    } else {
+        "[\n"
+    }
+}
+
+/// Display a [`Record`] as one [`Format::ChromeTrace`] event, without the
+/// trailing `,` and newline.
+pub(crate) struct ChromeTraceEvent<'a, 'b> {
+    record: &'a Record<'b>,
+    /// The event `"ts"`.
+    ts: Duration,
+    /// The event `"tid"`.
+    tid: usize,
+}
+
+impl<'a, 'b> ChromeTraceEvent<'a, 'b> {
+    /// Create a `ChromeTraceEvent` for `record` timestamped now.
+    pub(crate) fn new(record: &'a Record<'b>) -> Self {
+        let ts: Duration = CHROME_TRACE_START.elapsed();
+        let mut tids = CHROME_TRACE_TIDS.lock().unwrap();
+        let len: usize = tids.len();
+        let tid: usize = *tids.entry(record.thread_id).or_insert(len);
+
+        ChromeTraceEvent { record, ts, tid }
+    }
+}
+
+impl fmt::Display for ChromeTraceEvent<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let record = self.record;
+        let ph: &str = match record.kind {
+            Kind::Enter => "\"B\"",
+            Kind::Exit => "\"E\"",
+            Kind::EnterExit | Kind::Offset | Kind::Plain => "\"i\",\"s\":\"t\"",
+        };
+        f.write_str("{\"name\":\"")?;
+        JsonEscape(f).write_str(record.function_name)?;
+        write!(
+            f,
+            "\",\"cat\":\"{}\",\"ph\":{},\"ts\":{:.3},\"pid\":{},\"tid\":{},",
+            record.kind.as_str(),
+            ph,
+            self.ts.as_secs_f64() * 1_000_000.0,
+            std::process::id(),
+            self.tid,
+        )?;
+        f.write_str("\"args\":{\"function_full\":\"")?;
+        JsonEscape(f).write_str(record.function_name_full)?;
+        f.write_str("\",\"file\":\"")?;
+        JsonEscape(f).write_str(record.file)?;
+        write!(f, "\",\"line\":{},\"message\":\"", record.line)?;
+        write!(JsonEscape(f), "{}", record.message)?;
+        f.write_str("\"}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChromeTraceEvent, JsonLine};
+    use crate::record::{Kind, Record};
+    use std::fmt;
+    use std::time::Duration;
+
+    #[test]
+    fn test_json_line() {
+        // `message` is a parameter so its `format_args!` temporaries live
+        // for the entire call, as older compilers require
+        fn check(message: fmt::Arguments) {
+            let thread_id = std::thread::current().id();
+            let mut record = Record {
+                kind: Kind::EnterExit,
+                stack_offset: 3,
+                function_name: "func1",
+                function_name_full: "my_lib::func1",
+                thread_id,
+                thread_number: 2,
+                module_path: "my_lib",
+                file: "src\\lib.rs",
+                line: 12,
+                message,
+                elapsed: None,
+            };
+            assert_eq!(
+                JsonLine(&record).to_string(),
+                format!(
+                    concat!(
+                        r#"{{"kind":"enter_exit","depth":3,"function":"func1","function_full":"my_lib::func1","#,
+                        r#""thread":"{:?}","thread_number":2,"module_path":"my_lib","file":"src\\lib.rs","line":12,"#,
+                        r#""message":"a \"quoted\"\ttab\n\u0001","elapsed":null}}"#,
+                    ),
+                    thread_id
+                )
+            );
+            record.elapsed = Some(Duration::from_nanos(1_234_567));
+            assert!(JsonLine(&record).to_string().ends_with(r#","elapsed":1234.567}"#));
+        }
+        check(format_args!("a \"quoted\"\ttab\n{}", '\u{1}'));
+    }
+
+    #[test]
+    fn test_chrome_trace_event() {
+        // see `test_json_line`
+        fn check(message: fmt::Arguments) {
+            let mut record = Record {
+                kind: Kind::Enter,
+                stack_offset: 3,
+                function_name: "func1",
+                function_name_full: "my_lib::func1",
+                thread_id: std::thread::current().id(),
+                thread_number: 2,
+                module_path: "my_lib",
+                file: "src/lib.rs",
+                line: 12,
+                message,
+                elapsed: None,
+            };
+            let event = ChromeTraceEvent {
+                record: &record,
+                ts: Duration::from_nanos(8_114_250),
+                tid: 2,
+            };
+            assert_eq!(
+                event.to_string(),
+                format!(
+                    concat!(
+                        r#"{{"name":"func1","cat":"enter","ph":"B","ts":8114.250,"pid":{},"tid":2,"#,
+                        r#""args":{{"function_full":"my_lib::func1","file":"src/lib.rs","line":12,"message":"(3)"}}}}"#,
+                    ),
+                    std::process::id()
+                )
+            );
+            record.kind = Kind::EnterExit;
+            let event = ChromeTraceEvent {
+                record: &record,
+                ts: Duration::from_micros(9),
+                tid: 0,
+            };
+            assert!(event
+                .to_string()
+                .contains(r#""cat":"enter_exit","ph":"i","s":"t","ts":9.000,"#));
+        }
+        check(format_args!("({})", 3));
+    }
+
+    #[test]
+    fn test_chrome_trace_event_tid() {
+        let record = Record {
+            kind: Kind::Exit,
+            stack_offset: 0,
+            function_name: "func1",
+            function_name_full: "my_lib::func1",
+            thread_id: std::thread::current().id(),
+            thread_number: 0,
+            module_path: "my_lib",
+            file: "src/lib.rs",
+            line: 12,
+            message: format_args!(""),
+            elapsed: None,
+        };
+        let tid = ChromeTraceEvent::new(&record).tid;
+        assert_eq!(tid, ChromeTraceEvent::new(&record).tid);
+        let thread_id_other = std::thread::spawn(|| std::thread::current().id()).join().unwrap();
+        let record_other = Record {
+            thread_id: thread_id_other,
+            ..record
+        };
+        let tid_other = ChromeTraceEvent::new(&record_other).tid;
+        assert_ne!(tid, tid_other);
+    }
+}
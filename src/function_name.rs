@@ -82,13 +82,10 @@ macro_rules! function_name_plus {
             let len = name[..at].len();
             // get the index offset of the first rfind
             at = match rfind {
-                Some(index) => {
-                    if index < len {
-                        index
-                    } else {
-                        // this `else` should never happen but do not panic
-                        len
-                    }
+                Some(index) if index < len => index,
+                Some(_) => {
+                    // this should never happen but do not panic
+                    len
                 }
                 None => {
                     // this `None` should never happen but fallback to full name
@@ -134,10 +131,137 @@ macro_rules! function_name_full {
 }
 pub use function_name_full;
 
+/// Return the current method name preceded by its `Self` type as a
+/// `&'static str`, e.g. `"Foo::parse"` within `impl Foo`, or
+/// `"<Foo as Parse>::parse"` within `impl Parse for Foo`.
+///
+/// Module paths and generic arguments are removed, e.g. within
+/// `impl<T> Parse for my_mod::Foo<T>` returns `"<Foo as Parse>::parse"`.
+/// Within a function that is not a method, e.g. a free function, a nested
+/// function or a closure, returns the same as [`function_name!()`].
+///
+/// A type is distinguished from a module by an uppercase first letter, as
+/// `std::any::type_name` does not tell them apart. So a method of a type
+/// with a lowercase name, e.g. `impl foo`, returns only the method name,
+/// e.g. `"parse"`, and a function in a module with an uppercase name, e.g.
+/// `mod Foo`, returns the module name like a type, e.g. `"Foo::parse"`.
+/// Trait methods, e.g. `"<foo as Parse>::parse"`, are not affected.
+///
+/// The name is derived once per call site.
+///
+/// `function_name_method` must be a macro (and not a function) to reliably
+/// use `std::any::type_name::<T>()` introspection.
+///
+/// [`function_name!()`]: function_name
+#[macro_export]
+macro_rules! function_name_method {
+    () => {{
+        static NAME: std::sync::Mutex<Option<&'static str>> = std::sync::Mutex::new(None);
+        // not within the closure below
+        let name_full: &'static str = $crate::function_name::function_name_full!();
+        let mut name = NAME.lock().unwrap_or_else(|err| err.into_inner());
+        *name.get_or_insert_with(|| Box::leak($crate::function_name::__method_name(name_full).into_boxed_str()))
+    }};
+}
+pub use function_name_method;
+
+/// Split `name` at each `::` that is not within `<` `>`, e.g.
+/// `<a::Foo as a::Parse>::parse` is `<a::Foo as a::Parse>`, `parse`.
+fn split_path(name: &str) -> Vec<&str> {
+    let mut segments: Vec<&str> = Vec::new();
+    let mut depth: usize = 0;
+    let mut start: usize = 0;
+    let mut previous: char = ' ';
+    for (index, c) in name.char_indices() {
+        match c {
+            '<' => depth += 1,
+            // not the `->` of a function pointer
+            '>' if previous != '-' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 && previous == ':' && index > start => {
+                segments.push(&name[start..index - 1]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+        // the second `:` of a `::` does not begin another `::`
+        previous = match (previous, c) {
+            (':', ':') => ' ',
+            _ => c,
+        };
+    }
+    segments.push(&name[start..]);
+
+    segments
+}
+
+/// Return the type name `type_` without module paths and generic
+/// arguments, e.g. `a::Foo<alloc::string::String>` is `Foo`.
+fn type_short(type_: &str) -> String {
+    let mut short = String::with_capacity(type_.len());
+    let mut depth: usize = 0;
+    let mut chars = type_.chars().peekable();
+    let mut previous: char = ' ';
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => depth += 1,
+            '>' if previous != '-' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 && chars.peek() == Some(&':') => {
+                chars.next();
+                // remove the preceding module
+                let len: usize = short.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_').len();
+                short.truncate(len);
+            }
+            _ if depth == 0 => short.push(c),
+            _ => {}
+        }
+        previous = c;
+    }
+
+    short
+}
+
+/// Return the method name preceded by its `Self` type of the function
+/// with full path `name` from [`function_name_full!`].
+/// See [`function_name_method!`].
+#[doc(hidden)]
+pub fn __method_name(name: &str) -> String {
+    let segments: Vec<&str> = split_path(name);
+    let (self_, method): (&str, &str) = match segments.as_slice() {
+        [.., self_, method] => (self_, method),
+        _ => return String::from(name),
+    };
+    if self_.starts_with('<') && self_.ends_with('>') {
+        let qualified: &str = &self_[1..self_.len() - 1];
+        // the ` as ` that is not within `<` `>`
+        let mut depth: usize = 0;
+        let mut previous: char = ' ';
+        for (index, c) in qualified.char_indices() {
+            match c {
+                '<' => depth += 1,
+                '>' if previous != '-' => depth = depth.saturating_sub(1),
+                ' ' if depth == 0 && qualified[index..].starts_with(" as ") => {
+                    return format!(
+                        "<{} as {}>::{}",
+                        type_short(&qualified[..index]),
+                        type_short(&qualified[index + 4..]),
+                        method
+                    );
+                }
+                _ => {}
+            }
+            previous = c;
+        }
+        return format!("<{}>::{}", type_short(qualified), method);
+    }
+    if self_.starts_with(char::is_uppercase) {
+        format!("{}::{}", type_short(self_), method)
+    } else {
+        String::from(method)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{function_name, function_name_full, function_name_plus};
-
     #[test]
     fn test_function_name() {
         eprintln!("function_name! \"{}\"", function_name!());
@@ -224,6 +348,94 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_method_name() {
+        use super::__method_name;
+        assert_eq!(__method_name("tn::m::Foo<_>::parse"), "Foo::parse");
+        assert_eq!(
+            __method_name("<tn::m::Foo<u8> as tn::m::Parse>::parse"),
+            "<Foo as Parse>::parse"
+        );
+        assert_eq!(
+            __method_name("<tn::m::Foo<alloc::string::String> as core::fmt::Display>::fmt"),
+            "<Foo as Display>::fmt"
+        );
+        assert_eq!(
+            __method_name("<alloc::vec::Vec<_> as tn::m::Parse>::parse"),
+            "<Vec as Parse>::parse"
+        );
+        assert_eq!(__method_name("<&str as tn::m::Parse>::parse"), "<&str as Parse>::parse");
+        assert_eq!(
+            __method_name("<tn::Foo<fn() -> u8> as tn::From<u8>>::from"),
+            "<Foo as From>::from"
+        );
+        assert_eq!(__method_name("<[u8]>::parse"), "<[u8]>::parse");
+        assert_eq!(__method_name("tn::m::Foo<_>::parse::{{closure}}"), "{{closure}}");
+        assert_eq!(__method_name("tn::m::Foo<_>::parse::inner"), "inner");
+        assert_eq!(__method_name("tn::main"), "main");
+        assert_eq!(__method_name("main"), "main");
+    }
+
+    struct Foo<T>(T);
+
+    trait Parse {
+        fn parse(&self) -> &'static str;
+    }
+
+    impl<T> Foo<T> {
+        fn parse(&self) -> &'static str {
+            function_name_method!()
+        }
+    }
+
+    impl Parse for Foo<u8> {
+        fn parse(&self) -> &'static str {
+            function_name_method!()
+        }
+    }
+
+    #[test]
+    fn test_function_name_method() {
+        assert_eq!(Foo(()).parse(), "Foo::parse");
+        assert_eq!(Parse::parse(&Foo(1)), "<Foo as Parse>::parse");
+        fn func1() -> &'static str {
+            function_name_method!()
+        }
+        assert_eq!(func1(), "func1");
+        assert_eq!(function_name_method!(), "test_function_name_method");
+    }
+
+    #[allow(non_camel_case_types)]
+    struct foo;
+
+    impl foo {
+        fn parse(&self) -> &'static str {
+            function_name_method!()
+        }
+    }
+
+    impl Parse for foo {
+        fn parse(&self) -> &'static str {
+            function_name_method!()
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod Bar {
+        pub fn parse() -> &'static str {
+            function_name_method!()
+        }
+    }
+
+    #[test]
+    fn test_function_name_method_case() {
+        // a lowercase type is taken as a module
+        assert_eq!(foo.parse(), "parse");
+        assert_eq!(Parse::parse(&foo), "<foo as Parse>::parse");
+        // an uppercase module is taken as a type
+        assert_eq!(Bar::parse(), "Bar::parse");
+    }
+
     #[test]
     fn test_function_name_full() {
         eprintln!("function_name_full! \"{}\"", function_name_full!());
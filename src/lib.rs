@@ -116,6 +116,10 @@
 //! ←goodbye from main
 //! ```
 //!
+//! To print the enter and exit of a function without matching `defn!` and
+//! `defx!` statements, see attributes `#[si_trace]` and `#[si_trace_impl]` of
+//! companion crate `si_trace_print_macros`.
+//!
 //! ## Multi-threaded printing and the global lock
 //!
 //! To avoid interleaved printing among threads, surround your `println!` or
@@ -178,13 +182,126 @@
 //! }
 //! ```
 //!
+//! ## Disabling printing at runtime
+//!
+//! Run a program with environment variable `SI_TRACE=off` to disable all
+//! macro printing. Disabled macros do not evaluate their arguments.
+//! Or call [`set_enabled`].
+//!
+//! `SI_TRACE` may also filter by module or function, similar to `RUST_LOG`,
+//! e.g. `SI_TRACE=mycrate::parser=off,mycrate::net::connect=on`.
+//! See [`set_filter`].
+//!
+//! ## Cargo features
+//!
+//! - `off`: all macros print nothing, in debug and release builds.
+//!   Macro arguments are not evaluated.
+//!   Useful for a build where trace printing must be compiled out regardless
+//!   of the build profile.
+//! - `always`: the ***d***ebug macros, e.g. `defn!`, also print in release
+//!   builds. Useful for tracing optimized builds.
+//!
+//! If both features are enabled then `off` wins.
+//!
+//! ```toml
+//! [dependencies]
+//! si_trace_print = { version = "0.3", features = ["always"] }
+//! ```
+//!
+//! ## Printing to a writer
+//!
+//! All macros print to *stdout* or *stderr* unless a different writer is set
+//! with [`set_writer`], e.g. a file or an in-memory buffer.
+//! Call [`reset_writer`] to print to *stdout* and *stderr* again.
+//!
+//! ```rust
+//! use ::si_trace_print::printers::{reset_writer, set_writer};
+//! use ::si_trace_print::{defn, defx};
+//!
+//! let file = std::fs::File::create(std::env::temp_dir().join("trace.txt")).unwrap();
+//! set_writer(Box::new(file));
+//! defn!("printed to trace.txt");
+//! defx!("printed to trace.txt");
+//! reset_writer();
+//! ```
+//!
+//! To print a sequence number, elapsed time, timestamp or thread name at the
+//! start of each line, call [`set_prefix`].
+//! To print each thread in its own colored column, call
+//! [`set_thread_columns`].
+//! To print the source file and line after the function name, e.g.
+//! `→func1@src/lib.rs:12: (3)`, call [`set_location`].
+//!
+//! To capture printing within tests, call [`capture`].
+//!
+//! To consume each print as a structured [`Record`], call [`set_hook`].
+//!
+//! To print machine-readable JSON Lines or Chrome Trace Event JSON, call
+//! [`set_format`].
+//!
+//! To write folded stacks for flamegraph generation, see [`folded`].
+//!
+//! To indent by counting enter and exit macros instead of walking the stack,
+//! call [`set_depth_mode`]. This is much faster and is not affected by
+//! inlining in `--release` builds.
+//! Or, to count only stack frames within your own crates, so indentation
+//! does not need a [`stack_offset_set`] correction, see [`DepthMode::Crates`].
+//! To count inlined functions in optimized builds with debug info, see
+//! [`DepthMode::Inlined`].
+//!
+//! There is no limit to the indentation. To also print the stack offset of
+//! deep printing, e.g. `[42]`, call [`set_depth_marker`].
+//!
+//! To change the indentation, e.g. to two spaces, call [`set_indent_unit`].
+//! To change the leading symbols, e.g. to ASCII `>`, `<`, `=`, call
+//! [`set_leads`]. To print vertical guides like a tree, e.g. `│   ├─→func2`,
+//! call [`set_style`].
+//!
+//! Printing to a terminal is colored unless environment variable `NO_COLOR`
+//! is set. To always or never color, call [`set_color`].
+//!
 //! [`printers`]: crate::printers
+//! [`capture`]: crate::printers::capture
+//! [`set_prefix`]: crate::printers::set_prefix
+//! [`set_thread_columns`]: crate::printers::set_thread_columns
+//! [`set_location`]: crate::printers::set_location
+//! [`Record`]: crate::record::Record
+//! [`set_hook`]: crate::record::set_hook
+//! [`set_format`]: crate::format::set_format
+//! [`folded`]: crate::folded
+//! [`set_depth_mode`]: crate::stack::set_depth_mode
+//! [`stack_offset_set`]: crate::stack::stack_offset_set
+//! [`DepthMode::Crates`]: crate::stack::DepthMode::Crates
+//! [`DepthMode::Inlined`]: crate::stack::DepthMode::Inlined
+//! [`set_depth_marker`]: crate::stack::set_depth_marker
+//! [`set_indent_unit`]: crate::stack::set_indent_unit
+//! [`set_leads`]: crate::stack::set_leads
+//! [`set_style`]: crate::stack::set_style
+//! [`set_color`]: crate::color::set_color
+//! [`set_enabled`]: crate::printers::set_enabled
+//! [`set_filter`]: crate::printers::set_filter
+//! [`set_writer`]: crate::printers::set_writer
+//! [`reset_writer`]: crate::printers::reset_writer
 //! [`GLOBAL_LOCK_PRINTER`]: struct@crate::printers::GLOBAL_LOCK_PRINTER
 //! [`print_guard`]: crate::printers::print_guard
 //! [`debug_print_guard`]: crate::printers::debug_print_guard
 
 #![allow(uncommon_codepoints)]
 
+pub mod color;
+pub mod folded;
+pub mod format;
 pub mod function_name;
 pub mod printers;
+pub mod record;
 pub mod stack;
+
+/// Serialize tests that change global settings, e.g. [`set_elapsed`], or that
+/// compare printed output affected by global settings.
+///
+/// [`set_elapsed`]: crate::printers::set_elapsed
+#[cfg(test)]
+pub(crate) fn test_settings_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|err| err.into_inner())
+}
@@ -7,12 +7,33 @@
 //! These macros call [`sn`], [`so`], [`sx`], [`sñ`], for the preprinted
 //! indentation and signifier symbol.
 //!
+//! All macros print to stdout (`p*`, `dp*`) or stderr (`e*`, `de*`) unless
+//! another writer is set with [`set_writer`].
+//!
 //! [`sn`]: crate::stack::sn
 //! [`so`]: crate::stack::so
 //! [`sx`]: crate::stack::sx
 //! [`sñ`]: crate::stack::sñ
+//! [`set_writer`]: set_writer
+
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use std::sync::{Mutex, MutexGuard};
+use crate::color;
+use crate::folded;
+use crate::format;
+use crate::format::{ChromeTraceEvent, Format, JsonLine};
+use crate::record::{call_hook, Kind, Record};
+use crate::stack;
+use crate::stack::{DepthMode, StackDepth};
 
 extern crate lazy_static;
 use lazy_static::lazy_static;
@@ -42,29 +63,1237 @@ lazy_static! {
 
 /// Merely a wrapper around [`GLOBAL_LOCK_PRINTER.lock().unwrap()`].
 ///
+/// If a [hook] or [writer] panicked while printing then the lock is poisoned;
+/// the guard is still returned so that printing, e.g. from a guard dropped
+/// while unwinding, does not panic again.
+///
 /// [`GLOBAL_LOCK_PRINTER.lock().unwrap()`]: struct@GLOBAL_LOCK_PRINTER
+/// [hook]: crate::record::set_hook
+/// [writer]: set_writer
 #[inline(always)]
 pub fn print_guard() -> GlobalLockPrinterGuardType<'static> {
-    GLOBAL_LOCK_PRINTER.lock().unwrap()
+    GLOBAL_LOCK_PRINTER.lock().unwrap_or_else(|err| err.into_inner())
 }
 
-/// In debug builds, return the [`GLOBAL_LOCK_PRINTER`] guard.
+/// In debug builds, or with feature `always`, return the
+/// [`GLOBAL_LOCK_PRINTER`] guard.
 /// In non-debug builds this will be defined to return `()`.
 ///
 /// [`GLOBAL_LOCK_PRINTER`]: struct@GLOBAL_LOCK_PRINTER
-#[cfg(debug_assertions)]
+#[cfg(any(debug_assertions, feature = "always"))]
 #[inline(always)]
 pub fn debug_print_guard() -> GlobalLockPrinterGuardType<'static> {
     print_guard()
 }
 /// In debug builds, do nothing.
 // XXX: this docstring is not published
-#[cfg(not(debug_assertions))]
+#[cfg(not(any(debug_assertions, feature = "always")))]
 #[inline(always)]
 pub fn debug_print_guard() -> () {
     ()
 }
 
+/// A writer that receives all printing from `si_trace_print` macros.
+/// See [`set_writer`].
+pub type Writer = Box<dyn Write + Send>;
+
+lazy_static! {
+    /// The writer set by [`set_writer`]. If `None` then macros print to
+    /// stdout or stderr.
+    static ref WRITER: Mutex<Option<Writer>> = Mutex::new(None);
+}
+
+/// Send all printing from `si_trace_print` macros to `writer`.
+///
+/// This includes the `p*` and `dp*` macros that otherwise print to stdout
+/// and the `e*` and `de*` macros that otherwise print to stderr.
+/// Each printed line is written with one call to [`write_fmt`] and then
+/// the `writer` is flushed. Write errors are ignored.
+///
+/// Returns the previously set writer, if any.
+///
+/// ```rust
+/// use std::io::Write;
+/// use std::sync::{Arc, Mutex};
+/// use si_trace_print::printers::{reset_writer, set_writer};
+/// use si_trace_print::{efn, efx, pfo};
+///
+/// #[derive(Clone, Default)]
+/// struct Buffer(Arc<Mutex<Vec<u8>>>);
+///
+/// impl Write for Buffer {
+///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+///         self.0.lock().unwrap().write(buf)
+///     }
+///     fn flush(&mut self) -> std::io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let buffer = Buffer::default();
+/// set_writer(Box::new(buffer.clone()));
+/// efn!("hello");
+/// pfo!("stdout and stderr printing both go to the buffer");
+/// efx!("goodbye");
+/// reset_writer();
+///
+/// let printed = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+/// # #[cfg(not(feature = "off"))]
+/// assert_eq!(printed.lines().count(), 3);
+/// ```
+///
+/// [`write_fmt`]: std::io::Write::write_fmt
+pub fn set_writer(writer: Writer) -> Option<Writer> {
+    WRITER.lock().unwrap_or_else(|err| err.into_inner()).replace(writer)
+}
+
+/// Undo [`set_writer`]. Macros will print to stdout and stderr.
+///
+/// Returns the previously set writer, if any.
+pub fn reset_writer() -> Option<Writer> {
+    WRITER.lock().unwrap_or_else(|err| err.into_inner()).take()
+}
+
+/// Environment variable read once at the first macro call. The value is a
+/// filter of which functions print, see [`set_filter`].
+///
+/// For example, run a program with `SI_TRACE=off` to disable all printing.
+pub const ENV_SI_TRACE: &str = "SI_TRACE";
+
+/// Which functions print. See [`set_filter`].
+#[derive(Debug, PartialEq, Eq)]
+struct Filter {
+    /// Do functions not matched by any directive print?
+    default: bool,
+    /// Function path prefix and whether matching functions print.
+    /// Longest path first.
+    directives: Vec<(String, bool)>,
+}
+
+/// Parse an `on` or `off` filter value.
+fn parse_on_off(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "on" | "1" | "true" => Some(true),
+        "off" | "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+impl Filter {
+    /// Parse a [`set_filter`] `filter`. Invalid directives are ignored.
+    fn parse(filter: &str) -> Filter {
+        let mut default: bool = true;
+        let mut directives: Vec<(String, bool)> = Vec::new();
+        for directive in filter.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((path, value)) => {
+                    if let Some(on) = parse_on_off(value) {
+                        directives.push((String::from(path.trim()), on));
+                    }
+                }
+                None => match parse_on_off(directive) {
+                    Some(on) => default = on,
+                    None => directives.push((String::from(directive), true)),
+                },
+            }
+        }
+        directives.sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
+
+        Filter { default, directives }
+    }
+
+    /// Does function `function_name_full` print?
+    fn enabled(&self, function_name_full: &str) -> bool {
+        // trait impl methods, e.g. `<mycrate::Foo as core::fmt::Display>::fmt`,
+        // match by the implementing type `mycrate::Foo`
+        let (function_name_full, trait_impl): (&str, bool) = match function_name_full.strip_prefix('<') {
+            Some(function_name_full) => (function_name_full, true),
+            None => (function_name_full, false),
+        };
+        for (path, on) in self.directives.iter() {
+            if let Some(rest) = function_name_full.strip_prefix(path.as_str()) {
+                // a path followed by `<` is a generic type, e.g. `mycrate::Foo<T>::new`
+                if rest.is_empty()
+                    || rest.starts_with("::")
+                    || rest.starts_with('<')
+                    || (trait_impl && rest.starts_with(" as "))
+                {
+                    return *on;
+                }
+            }
+        }
+
+        self.default
+    }
+}
+
+lazy_static! {
+    /// The filter set by [`set_filter`] or [`set_enabled`], initially from
+    /// environment variable [`ENV_SI_TRACE`].
+    static ref FILTER: RwLock<Filter> =
+        RwLock::new(Filter::parse(&std::env::var(ENV_SI_TRACE).unwrap_or_default()));
+}
+
+/// Set which functions print using a filter similar to `RUST_LOG`.
+///
+/// The `filter` is a comma-separated list of directives. Each directive
+/// is one of
+/// - `on` or `off`: the default for all functions. The default is `on`.
+/// - `path=on` or `path=off`: functions with a [`function_name_full!`]
+///   starting with `path`.
+/// - `path`: same as `path=on`.
+///
+/// The longest matching `path` is used. A `path` matches whole names,
+/// e.g. `mycrate::net` matches `mycrate::net::connect` but not
+/// `mycrate::network`. Trait impl methods match by the implementing type,
+/// e.g. `mycrate::parser` and `mycrate::parser::Foo` match
+/// `<mycrate::parser::Foo as core::fmt::Display>::fmt`. Methods of generic
+/// types match by the type, e.g. `mycrate::parser::Foo` matches
+/// `mycrate::parser::Foo<_>::new`.
+///
+/// For example, `mycrate::parser=off,mycrate::net::connect=on` silences
+/// module `mycrate::parser`, and `off,mycrate::net=on` prints only module
+/// `mycrate::net`.
+///
+/// The initial filter is read from environment variable [`ENV_SI_TRACE`] at
+/// the first macro call.
+///
+/// Filtered macros do nothing and do not evaluate their arguments.
+///
+/// ```rust,no_run
+/// use si_trace_print::printers::set_filter;
+///
+/// // print only module `mycrate::net` except function `mycrate::net::poll`
+/// set_filter("off,mycrate::net,mycrate::net::poll=off");
+/// ```
+///
+/// [`function_name_full!`]: crate::function_name::function_name_full
+pub fn set_filter(filter: &str) {
+    *FILTER.write().unwrap() = Filter::parse(filter);
+}
+
+/// Enable or disable macro printing for all functions not matched by a
+/// [`set_filter`] `path` directive.
+///
+/// When disabled, macros do nothing and do not evaluate their arguments.
+///
+/// Returns the previous setting.
+///
+/// ```rust
+/// use si_trace_print::printers::set_enabled;
+/// use si_trace_print::pfo;
+///
+/// set_enabled(false);
+/// pfo!("not printed {}", unreachable!());
+/// set_enabled(true);
+/// pfo!("printed");
+/// ```
+pub fn set_enabled(enabled: bool) -> bool {
+    std::mem::replace(&mut FILTER.write().unwrap().default, enabled)
+}
+
+/// Does the function `function_name_full` print? See [`set_filter`].
+#[doc(hidden)]
+#[inline]
+pub fn __enabled(function_name_full: &str) -> bool {
+    FILTER.read().unwrap().enabled(function_name_full)
+}
+
+/// Print elapsed time on exit lines? See [`set_elapsed`].
+static ELAPSED: AtomicBool = AtomicBool::new(false);
+
+/// Append the time elapsed since entering a function to exit lines, e.g.
+///
+/// ```text
+/// ←func1: (3) [1.24ms]
+/// ```
+///
+/// The elapsed time is measured from the enter line (e.g. `pfn!`, `defn!`)
+/// printed by the same thread in the same function at the same stack offset.
+/// A guard's exit line (e.g. from `defg!`) is measured from the guard's own
+/// enter line.
+/// It is also passed to the [`set_hook`] hook as [`Record::elapsed`].
+/// Disabled by default.
+///
+/// Returns the previous setting.
+///
+/// [`set_hook`]: crate::record::set_hook
+/// [`Record::elapsed`]: crate::record::Record::elapsed
+pub fn set_elapsed(elapsed: bool) -> bool {
+    ELAPSED.swap(elapsed, Ordering::SeqCst)
+}
+
+/// Display the [`set_elapsed`] time appended to an exit line, if any.
+struct ElapsedSuffix<'a> {
+    elapsed: Option<Duration>,
+    /// Printed before the elapsed time.
+    separator: &'a str,
+}
+
+impl fmt::Display for ElapsedSuffix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.elapsed {
+            Some(elapsed) => write!(f, "{}[{:.2?}]", self.separator, elapsed),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Fields printed at the start of each line, before the indentation.
+/// See [`set_prefix`].
+///
+/// More fields may be added, so create a `Prefix` from [`Prefix::NONE`] or
+/// [`Prefix::ALL`] and the `with_*` methods.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Prefix {
+    /// A global sequence number of printed lines, e.g. `    42`.
+    pub sequence: bool,
+    /// Time elapsed since the first macro print, e.g. `    1.234567s`.
+    pub elapsed: bool,
+    /// Wall-clock time in UTC, e.g. `2024-01-31T12:34:56.123456Z`.
+    pub timestamp: bool,
+    /// The thread name or, for unnamed threads, a short thread number
+    /// in order of first macro print, e.g. `main` or `T3`.
+    pub thread: bool,
+}
+
+impl Prefix {
+    /// No prefix. The default.
+    pub const NONE: Prefix = Prefix {
+        sequence: false,
+        elapsed: false,
+        timestamp: false,
+        thread: false,
+    };
+
+    /// All fields.
+    pub const ALL: Prefix = Prefix {
+        sequence: true,
+        elapsed: true,
+        timestamp: true,
+        thread: true,
+    };
+
+    /// Set [`Prefix::sequence`].
+    pub const fn with_sequence(self, sequence: bool) -> Prefix {
+        Prefix { sequence, ..self }
+    }
+
+    /// Set [`Prefix::elapsed`].
+    pub const fn with_elapsed(self, elapsed: bool) -> Prefix {
+        Prefix { elapsed, ..self }
+    }
+
+    /// Set [`Prefix::timestamp`].
+    pub const fn with_timestamp(self, timestamp: bool) -> Prefix {
+        Prefix { timestamp, ..self }
+    }
+
+    /// Set [`Prefix::thread`].
+    pub const fn with_thread(self, thread: bool) -> Prefix {
+        Prefix { thread, ..self }
+    }
+}
+
+lazy_static! {
+    /// See [`set_prefix`].
+    static ref PREFIX: RwLock<Prefix> = RwLock::new(Prefix::NONE);
+    /// The first macro print. See [`Prefix::elapsed`].
+    static ref PREFIX_START: Instant = Instant::now();
+}
+
+/// Sequence number of the next printed line. See [`Prefix::sequence`].
+static SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+/// The next [`thread_number`].
+static THREAD_NUMBER_NEXT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// See [`thread_number`].
+    static THREAD_NUMBER: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Return a small number for the current thread, assigned in order of
+/// first call, starting from `0`.
+pub(crate) fn thread_number() -> usize {
+    THREAD_NUMBER.with(|number| match number.get() {
+        Some(number) => number,
+        None => {
+            let number_ = THREAD_NUMBER_NEXT.fetch_add(1, Ordering::Relaxed);
+            number.set(Some(number_));
+            number_
+        }
+    })
+}
+
+/// The next [`thread_column`] never assigned.
+static THREAD_COLUMN_NEXT: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// The [`thread_column`]s of exited threads, to be assigned again.
+    static ref THREAD_COLUMNS_FREE: Mutex<BinaryHeap<Reverse<usize>>> = Mutex::new(BinaryHeap::new());
+}
+
+/// Frees a [`thread_column`] when its thread exits.
+struct ThreadColumnFree(usize);
+
+impl Drop for ThreadColumnFree {
+    fn drop(&mut self) {
+        THREAD_COLUMNS_FREE
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(Reverse(self.0));
+    }
+}
+
+thread_local! {
+    /// See [`thread_column`].
+    static THREAD_COLUMN: Cell<Option<usize>> = const { Cell::new(None) };
+    /// See [`ThreadColumnFree`].
+    static THREAD_COLUMN_FREE: RefCell<Option<ThreadColumnFree>> = const { RefCell::new(None) };
+}
+
+/// Return the [`set_thread_columns`] column of the current thread, the
+/// smallest column not used by a running thread, assigned at the first call.
+pub(crate) fn thread_column() -> usize {
+    THREAD_COLUMN.with(|column| match column.get() {
+        Some(column) => column,
+        None => {
+            let column_: usize = match THREAD_COLUMNS_FREE.lock().unwrap_or_else(|err| err.into_inner()).pop() {
+                Some(Reverse(column_)) => column_,
+                None => THREAD_COLUMN_NEXT.fetch_add(1, Ordering::Relaxed),
+            };
+            column.set(Some(column_));
+            // if the thread is exiting, e.g. printing from a thread-local
+            // destructor, then the column is never freed
+            _ = THREAD_COLUMN_FREE.try_with(|free| *free.borrow_mut() = Some(ThreadColumnFree(column_)));
+            column_
+        }
+    })
+}
+
+lazy_static! {
+    /// See [`set_thread_columns`].
+    static ref THREAD_COLUMNS: RwLock<Option<usize>> = RwLock::new(None);
+}
+
+/// Print each thread in its own column `width` characters wide and, if
+/// colored by [`set_color`], in its own color, so concurrent call trees stay
+/// visually separate, e.g.
+///
+/// ```text
+/// →main:
+///  main: spawning
+///                                         →func1: (1)
+///                                                                                 →func1: (2)
+///                                         ←func1: (1)
+/// ```
+///
+/// Each thread takes the left-most column not used by a running thread, so
+/// columns do not grow wider than the running threads.
+/// Pass `None` to print all threads in the same column, the default.
+///
+/// Only affects [`Format::Text`]. The [`Prefix`] is printed before the
+/// column. The color of the thread replaces the colors of leads, function
+/// names and indentation.
+///
+/// Returns the previous `width`.
+///
+/// [`set_color`]: crate::color::set_color
+pub fn set_thread_columns(width: Option<usize>) -> Option<usize> {
+    std::mem::replace(&mut *THREAD_COLUMNS.write().unwrap(), width)
+}
+
+/// Display the [`set_thread_columns`] column of the current thread, if any,
+/// and its color if `colored`.
+struct ThreadColumn {
+    width: Option<usize>,
+    /// See [`thread_column`].
+    column: usize,
+    colored: bool,
+}
+
+impl fmt::Display for ThreadColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.width {
+            Some(width) => write!(
+                f,
+                "{}{:indent$}",
+                if self.colored { color::cycle(self.column) } else { "" },
+                "",
+                indent = width * self.column
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Print fields at the start of each line printed in [`Format::Text`], e.g.
+///
+/// ```text
+///      7     0.000412s 2024-01-31T12:34:56.123456Z main     →func1: (3)
+/// ```
+///
+/// Useful to untangle printing from several threads.
+///
+/// Returns the previous prefix.
+///
+/// ```rust
+/// use si_trace_print::printers::{set_prefix, Prefix};
+///
+/// set_prefix(Prefix::NONE.with_thread(true).with_sequence(true));
+/// ```
+pub fn set_prefix(prefix: Prefix) -> Prefix {
+    std::mem::replace(&mut *PREFIX.write().unwrap(), prefix)
+}
+
+/// Display a time as an RFC 3339 UTC timestamp with microseconds, e.g.
+/// `2024-01-31T12:34:56.123456Z`.
+struct Timestamp(SystemTime);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let since: Duration = self.0.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs: u64 = since.as_secs();
+        let (hour, minute, second) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+        // civil date from days since 1970-01-01, see
+        // <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+        let z: u64 = secs / 86400 + 719468;
+        let era: u64 = z / 146097;
+        let doe: u64 = z - era * 146097;
+        let yoe: u64 = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy: u64 = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp: u64 = (5 * doy + 2) / 153;
+        let day: u64 = doy - (153 * mp + 2) / 5 + 1;
+        let month: u64 = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year: u64 = yoe + era * 400 + (month <= 2) as u64;
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            since.subsec_micros()
+        )
+    }
+}
+
+/// Display the [`set_prefix`] fields of one line.
+struct PrefixFields {
+    prefix: Prefix,
+    sequence: usize,
+}
+
+impl fmt::Display for PrefixFields {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.prefix.sequence {
+            write!(f, "{:>6} ", self.sequence)?;
+        }
+        if self.prefix.elapsed {
+            let elapsed: Duration = PREFIX_START.elapsed();
+            write!(f, "{:>5}.{:06}s ", elapsed.as_secs(), elapsed.subsec_micros())?;
+        }
+        if self.prefix.timestamp {
+            write!(f, "{} ", Timestamp(SystemTime::now()))?;
+        }
+        if self.prefix.thread {
+            match thread::current().name() {
+                Some(name) => write!(f, "{:<8} ", name)?,
+                None => write!(f, "{:<8} ", format!("T{}", thread_number()))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The source location printed after the function name. See
+/// [`set_location`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Location {
+    /// Do not print the source location. The default.
+    #[default]
+    Off,
+    /// Print [`file!`] and [`line!`] as-is, e.g.
+    /// `→func1@crates/parser/src/lexer.rs:42: (3)`.
+    Full,
+    /// Print [`file!`] relative to the crate root and [`line!`], e.g.
+    /// `→func1@src/lexer.rs:42: (3)`.
+    Short,
+}
+
+lazy_static! {
+    /// See [`set_location`].
+    static ref LOCATION: RwLock<Location> = RwLock::new(Location::default());
+}
+
+/// Print the source location of each macro after the function name, e.g.
+///
+/// ```text
+/// →func1@src/lib.rs:12: (3)
+///  func1@src/lib.rs:14: parsing
+///  func1@src/lib.rs:17: parsed
+/// ```
+///
+/// Useful to tell apart several `defo!` in one function.
+/// Macros that do not print a function name print only the source location,
+/// e.g. `→src/lib.rs:12: hello`.
+///
+/// Only affects [`Format::Text`].
+///
+/// Returns the previous setting.
+///
+/// ```rust
+/// use si_trace_print::printers::{set_location, Location};
+///
+/// set_location(Location::Short);
+/// ```
+pub fn set_location(location: Location) -> Location {
+    std::mem::replace(&mut *LOCATION.write().unwrap(), location)
+}
+
+/// Return `file` relative to the crate root, i.e. from the last `src`
+/// directory, or else from the first `tests`, `examples` or `benches`
+/// directory, e.g. `crates/parser/src/lexer.rs` is `src/lexer.rs`.
+/// Otherwise return `file`.
+fn file_short(file: &str) -> &str {
+    let mut start: Option<usize> = None;
+    let mut offset: usize = 0;
+    for component in file.split(['/', '\\']) {
+        match component {
+            "src" => start = Some(offset),
+            "tests" | "examples" | "benches" if start.is_none() => start = Some(offset),
+            _ => {}
+        }
+        offset += component.len() + 1;
+    }
+
+    &file[start.unwrap_or(0)..]
+}
+
+/// Display the function name of a line and its [`set_location`] source
+/// location, if any.
+struct CallsiteName {
+    name: Option<&'static str>,
+    /// The file and line.
+    location: Option<(&'static str, u32)>,
+}
+
+impl CallsiteName {
+    /// Does this display nothing?
+    const fn is_empty(&self) -> bool {
+        self.name.is_none() && self.location.is_none()
+    }
+}
+
+impl fmt::Display for CallsiteName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.name, self.location) {
+            (Some(name), Some((file, line))) => write!(f, "{}@{}:{}", name, file, line),
+            (Some(name), None) => f.write_str(name),
+            (None, Some((file, line))) => write!(f, "{}:{}", file, line),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+thread_local! {
+    /// Buffers of the current thread's active [`capture`] calls.
+    /// The last buffer is the innermost `capture`.
+    static CAPTURES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Pops the innermost [`CAPTURES`] buffer when dropped, including during
+/// a panic within the captured function.
+struct CaptureGuard;
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        CAPTURES.with(|captures| captures.borrow_mut().pop());
+    }
+}
+
+/// Call `f` and return everything printed by `si_trace_print` macros on
+/// the current thread during `f`.
+///
+/// Captured printing is not printed to stdout, stderr, or the
+/// [`set_writer`] writer. Printing from other threads is not captured.
+/// Calls to `capture` may be nested; the innermost `capture` receives the
+/// printing.
+///
+/// Useful for tests that compare printed trace output.
+///
+/// ```rust
+/// use si_trace_print::printers::capture;
+/// use si_trace_print::{defn, defo, defx};
+///
+/// fn func1() {
+///     defn!();
+///     func2();
+///     defx!();
+/// }
+/// fn func2() {
+///     defo!("hello");
+/// }
+///
+/// let printed = std::thread::spawn(|| capture(func1)).join().unwrap();
+/// # #[cfg(not(feature = "off"))]
+/// assert_eq!(printed, "→func1: \n     func2: hello\n←func1: \n");
+/// ```
+pub fn capture<F: FnOnce()>(f: F) -> String {
+    CAPTURES.with(|captures| captures.borrow_mut().push(String::new()));
+    let guard = CaptureGuard;
+    f();
+    let printed = CAPTURES.with(|captures| captures.borrow_mut().last_mut().map(std::mem::take));
+    drop(guard);
+
+    printed.unwrap_or_default()
+}
+
+/// If the current thread is within a [`capture`] then append `args` to the
+/// innermost capture buffer and return `true`.
+fn print_capture(args: fmt::Arguments) -> bool {
+    // `CAPTURES` is destroyed when printing from a later thread-local
+    // destructor
+    CAPTURES
+        .try_with(|captures| match captures.borrow_mut().last_mut() {
+            Some(buffer) => {
+                _ = buffer.write_fmt(args);
+                true
+            }
+            None => false,
+        })
+        .unwrap_or(false)
+}
+
+/// Print `args` to the current [`capture`], the [`set_writer`] writer, or
+/// else stdout.
+fn print_stdout(args: fmt::Arguments) {
+    if print_capture(args) {
+        return;
+    }
+    match WRITER.lock().unwrap_or_else(|err| err.into_inner()).as_mut() {
+        Some(writer) => {
+            _ = writer.write_fmt(args);
+            _ = writer.flush();
+        }
+        None => print!("{}", args),
+    }
+}
+
+/// Print `args` to the current [`capture`], the [`set_writer`] writer, or
+/// else stderr.
+fn print_stderr(args: fmt::Arguments) {
+    if print_capture(args) {
+        return;
+    }
+    match WRITER.lock().unwrap_or_else(|err| err.into_inner()).as_mut() {
+        Some(writer) => {
+            _ = writer.write_fmt(args);
+            _ = writer.flush();
+        }
+        None => eprint!("{}", args),
+    }
+}
+
+/// Is printing to `stream` colored? See [`set_color`].
+///
+/// [`set_color`]: crate::color::set_color
+fn colored(stream: Stream) -> bool {
+    let fd: i32 = match stream {
+        Stream::Stdout => 1,
+        Stream::Stderr => 2,
+    };
+    color::enabled(fd, || {
+        !CAPTURES
+            .try_with(|captures| captures.borrow().is_empty())
+            .unwrap_or(true)
+            || WRITER.lock().unwrap_or_else(|err| err.into_inner()).is_some()
+    })
+}
+
+/// The standard stream a macro prints to when no [`set_writer`] writer is
+/// set.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    /// `p*` and `dp*` macros.
+    Stdout,
+    /// `e*` and `de*` macros.
+    Stderr,
+}
+
+/// Information about a macro call site. Created by [`__si_callsite!`].
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct Callsite {
+    pub stream: Stream,
+    pub kind: Kind,
+    /// The function name to print, if any.
+    pub name: Option<&'static str>,
+    pub function_name: &'static str,
+    pub function_name_full: &'static str,
+    pub module_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+}
+
+/// Print one trace line for the macro at `callsite` with message `args`.
+///
+/// Must not be inlined so the stack offset is consistent with [`so()`],
+/// [`sn()`], [`sx()`], [`sñ()`].
+///
+/// [`so()`]: crate::stack::so
+/// [`sn()`]: crate::stack::sn
+/// [`sx()`]: crate::stack::sx
+/// [`sñ()`]: crate::stack::sñ
+#[doc(hidden)]
+#[inline(never)]
+pub fn __print(callsite: &Callsite, args: fmt::Arguments) {
+    let stack_offset: StackDepth = match stack::depth_mode() {
+        // all macros, including `Kind::Plain` macros, set the "original" stack
+        // depth via `stack_offset`
+        DepthMode::Backtrace | DepthMode::Inlined => stack::stack_offset(),
+        DepthMode::Counter => stack::depth_counter(callsite.kind),
+        DepthMode::Crates(prefixes) => stack::crate_depth(prefixes),
+    };
+    print_record(callsite, stack_offset, false, args);
+}
+
+/// Print one trace line for the macro at `callsite` at `stack_offset` with
+/// message `args`. `guard` is set for the enter and exit lines of an
+/// [`ExitGuard`].
+///
+/// `args` is formatted before locking [`GLOBAL_LOCK_PRINTER`], as formatting
+/// may call a `Debug` or `Display` impl that also prints, e.g. one
+/// instrumented by `#[si_trace_impl]`.
+///
+/// [`GLOBAL_LOCK_PRINTER`]: struct@GLOBAL_LOCK_PRINTER
+fn print_record(callsite: &Callsite, stack_offset: StackDepth, guard: bool, args: fmt::Arguments) {
+    match args.as_str() {
+        // no arguments to format
+        Some(_) => print_record_locked(callsite, stack_offset, guard, args),
+        None => {
+            let message: String = fmt::format(args);
+            print_record_locked(callsite, stack_offset, guard, format_args!("{}", message));
+        }
+    }
+}
+
+/// See [`print_record`].
+fn print_record_locked(callsite: &Callsite, stack_offset: StackDepth, guard: bool, args: fmt::Arguments) {
+    let lock = print_guard();
+    lazy_static::initialize(&PREFIX_START);
+    let prefix = PrefixFields {
+        prefix: *PREFIX.read().unwrap(),
+        sequence: SEQUENCE.fetch_add(1, Ordering::Relaxed),
+    };
+    let elapsed: Option<Duration> = match (ELAPSED.load(Ordering::Relaxed), callsite.kind) {
+        (true, Kind::Enter) => {
+            stack::entry_push(
+                stack_offset,
+                callsite.function_name_full,
+                (callsite.file, callsite.line),
+                guard,
+            );
+            None
+        }
+        (true, Kind::Exit) => stack::entry_pop(
+            stack_offset,
+            callsite.function_name_full,
+            (callsite.file, callsite.line),
+            guard,
+        ),
+        _ => None,
+    };
+    let record = Record {
+        kind: callsite.kind,
+        stack_offset,
+        function_name: callsite.function_name,
+        function_name_full: callsite.function_name_full,
+        thread_id: thread::current().id(),
+        thread_number: thread_number(),
+        module_path: callsite.module_path,
+        file: callsite.file,
+        line: callsite.line,
+        message: args,
+        elapsed,
+    };
+    call_hook(&record);
+    folded::collect(&record);
+    let print = match callsite.stream {
+        Stream::Stdout => print_stdout,
+        Stream::Stderr => print_stderr,
+    };
+    match format::format() {
+        Format::Text => {
+            let colored: bool = colored(callsite.stream);
+            let width: Option<usize> = *THREAD_COLUMNS.read().unwrap();
+            // a colored thread column colors the entire line
+            let colored_parts: bool = colored && width.is_none();
+            let lead = stack::lead_at(stack_offset, callsite.kind, colored_parts);
+            let suffix = ElapsedSuffix {
+                elapsed,
+                separator: match args.as_str() {
+                    Some("") => "",
+                    _ => " ",
+                },
+            };
+            let column = ThreadColumn {
+                width,
+                column: thread_column(),
+                colored,
+            };
+            let (dim, undim): (&str, &str) = if colored_parts {
+                (color::DIM, color::kind(callsite.kind))
+            } else {
+                ("", "")
+            };
+            // a `Kind::Plain` message is not colored, so only a thread column
+            // leaves a color to reset
+            let end: &str = if colored && (width.is_some() || callsite.kind != Kind::Plain) {
+                color::RESET
+            } else {
+                ""
+            };
+            let name = CallsiteName {
+                name: callsite.name,
+                location: match *LOCATION.read().unwrap() {
+                    Location::Off => None,
+                    Location::Full => Some((callsite.file, callsite.line)),
+                    Location::Short => Some((file_short(callsite.file), callsite.line)),
+                },
+            };
+            if name.is_empty() {
+                print(format_args!("{}{}{}{}{}{}\n", prefix, column, lead, args, suffix, end));
+            } else {
+                print(format_args!(
+                    "{}{}{}{}{}{}: {}{}{}\n",
+                    prefix, column, lead, dim, name, undim, args, suffix, end
+                ));
+            }
+        }
+        Format::JsonLines => print(format_args!("{}\n", JsonLine(&record))),
+        Format::ChromeTrace => print(format_args!(
+            "{}{},\n",
+            format::chrome_trace_header(),
+            ChromeTraceEvent::new(&record)
+        )),
+    }
+    drop(lock);
+}
+
+/// Prints the exit line of a function when dropped, at the same stack offset
+/// as the enter line. Returned by guard macros, e.g. [`defg!`].
+///
+/// If dropped while the thread is panicking then the exit line has
+/// the message `[panicking]`.
+///
+/// [`defg!`]: crate::defg
+#[must_use = "the exit line is printed when the guard is dropped; bind it with `let _guard = ...`"]
+pub struct ExitGuard {
+    /// The exit line callsite and stack offset. `None` does nothing.
+    exit: Option<(Callsite, StackDepth)>,
+}
+
+impl ExitGuard {
+    /// An `ExitGuard` that prints nothing. Returned by debug guard macros in
+    /// non-debug builds.
+    #[doc(hidden)]
+    pub const fn none() -> ExitGuard {
+        ExitGuard { exit: None }
+    }
+
+    /// Print the exit line with message `args`, if not yet printed.
+    fn exit(&mut self, args: fmt::Arguments) {
+        if let Some((callsite, stack_offset)) = self.exit.take() {
+            if stack::depth_mode() == DepthMode::Counter {
+                stack::depth_counter(Kind::Exit);
+            }
+            print_record(&callsite, stack_offset, true, args);
+        }
+    }
+
+    /// Print the exit line with the returned `value` and return `value`.
+    /// Used by `#[si_trace]` for each `return` and the returned tail
+    /// expression.
+    #[doc(hidden)]
+    pub fn __return<T: fmt::Debug>(&mut self, value: T) -> T {
+        if self.exit.is_some() {
+            self.exit(format_args!("{:?}", value));
+        }
+
+        value
+    }
+
+    /// If the `?` operator will return `value` early then print the exit
+    /// line with `value`. Return `value`.
+    /// Used by `#[si_trace]` for each `?`.
+    #[doc(hidden)]
+    pub fn __branch<T: __Branch>(&mut self, value: T) -> T {
+        if self.exit.is_some() && value.__is_break() {
+            self.exit(format_args!("{}", BranchDisplay(&value)));
+        }
+
+        value
+    }
+}
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.exit(format_args!("[panicking]"));
+        } else {
+            self.exit(format_args!(""));
+        }
+    }
+}
+
+/// A type the `?` operator may return early from, printed by
+/// [`ExitGuard::__branch`].
+#[doc(hidden)]
+pub trait __Branch {
+    /// Will the `?` operator return early?
+    fn __is_break(&self) -> bool;
+    /// Format the early return.
+    fn __fmt_break(&self, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+impl<T, E: fmt::Debug> __Branch for Result<T, E> {
+    fn __is_break(&self) -> bool {
+        self.is_err()
+    }
+
+    fn __fmt_break(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ok(_) => Ok(()),
+            Err(err) => write!(f, "Err({:?})", err),
+        }
+    }
+}
+
+impl<T> __Branch for Option<T> {
+    fn __is_break(&self) -> bool {
+        self.is_none()
+    }
+
+    fn __fmt_break(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("None")
+    }
+}
+
+/// Display [`__Branch::__fmt_break`].
+struct BranchDisplay<'a, T>(&'a T);
+
+impl<T: __Branch> fmt::Display for BranchDisplay<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.__fmt_break(f)
+    }
+}
+
+/// Print the enter line for the function at `callsite` with message `args`.
+/// Return an [`ExitGuard`] to print the exit line.
+///
+/// Must not be inlined, see [`__print`].
+#[doc(hidden)]
+#[inline(never)]
+pub fn __print_enter(callsite: &Callsite, args: fmt::Arguments) -> ExitGuard {
+    let stack_offset: StackDepth = match stack::depth_mode() {
+        DepthMode::Backtrace | DepthMode::Inlined => stack::stack_offset(),
+        DepthMode::Counter => stack::depth_counter(Kind::Enter),
+        DepthMode::Crates(prefixes) => stack::crate_depth(prefixes),
+    };
+    print_record(callsite, stack_offset, true, args);
+    let exit = Callsite {
+        kind: Kind::Exit,
+        ..*callsite
+    };
+
+    ExitGuard {
+        exit: Some((exit, stack_offset)),
+    }
+}
+
+/// Create a [`Callsite`] for the calling function.
+///
+/// `$stream` is a [`Stream`] variant, `$kind` is a [`Kind`] variant,
+/// `$name` is the `Option` function name to print.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_callsite {
+    (
+        $stream:ident, $kind:ident, $name:expr
+    ) => {
+        $crate::printers::Callsite {
+            stream: $crate::printers::Stream::$stream,
+            kind: $crate::record::Kind::$kind,
+            name: $name,
+            function_name: $crate::function_name::function_name!(),
+            function_name_full: $crate::function_name::function_name_full!(),
+            module_path: module_path!(),
+            file: file!(),
+            line: line!(),
+        }
+    };
+}
+
+/// Call [`__print`] for the calling function if [`__enabled`].
+///
+/// `$stream`, `$kind`, `$name` are passed to [`__si_callsite!`], followed by
+/// the [`println!`] arguments.
+#[cfg(not(feature = "off"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_print {
+    (
+        $stream:ident, $kind:ident, $name:expr, $($args:tt)*
+    ) => {
+        if $crate::printers::__enabled($crate::function_name::function_name_full!()) {
+            $crate::printers::__print(
+                &$crate::__si_callsite!($stream, $kind, $name),
+                $crate::__si_format_args!($($args)*),
+            )
+        }
+    };
+}
+
+/// With feature `off`, never print.
+///
+/// The arguments are still type-checked, so variables used only in macros
+/// do not become unused, but they are never evaluated and the optimizer
+/// removes the dead branch.
+#[cfg(feature = "off")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_print {
+    (
+        $stream:ident, $kind:ident, $name:expr, $($args:tt)*
+    ) => {
+        if false {
+            $crate::printers::__print(
+                &$crate::__si_callsite!($stream, $kind, $name),
+                $crate::__si_format_args!($($args)*),
+            )
+        }
+    };
+}
+
+/// Call [`__print_enter`] for the calling function if [`__enabled`].
+/// Otherwise return [`ExitGuard::none`].
+///
+/// Arguments are the same as [`__si_print!`].
+#[cfg(not(feature = "off"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_print_enter {
+    (
+        $stream:ident, $kind:ident, $name:expr, $($args:tt)*
+    ) => {
+        if $crate::printers::__enabled($crate::function_name::function_name_full!()) {
+            $crate::printers::__print_enter(
+                &$crate::__si_callsite!($stream, $kind, $name),
+                $crate::__si_format_args!($($args)*),
+            )
+        } else {
+            $crate::printers::ExitGuard::none()
+        }
+    };
+}
+
+/// With feature `off`, never print and return [`ExitGuard::none`].
+#[cfg(feature = "off")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_print_enter {
+    (
+        $stream:ident, $kind:ident, $name:expr, $($args:tt)*
+    ) => {
+        if false {
+            $crate::printers::__print_enter(
+                &$crate::__si_callsite!($stream, $kind, $name),
+                $crate::__si_format_args!($($args)*),
+            )
+        } else {
+            $crate::printers::ExitGuard::none()
+        }
+    };
+}
+
+/// [`__si_print!`] only in debug builds or tests of the calling crate.
+///
+/// The `cfg` attributes within an exported macro are evaluated in the
+/// calling crate, so feature `always` must choose between definitions here.
+#[cfg(not(feature = "always"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_print_debug {
+    (
+        $($args:tt)*
+    ) => {
+        #[cfg(any(debug_assertions,test))]
+        $crate::__si_print!($($args)*);
+    };
+}
+
+/// With feature `always`, [`__si_print!`] in all builds.
+#[cfg(feature = "always")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_print_debug {
+    (
+        $($args:tt)*
+    ) => {
+        $crate::__si_print!($($args)*);
+    };
+}
+
+/// [`__si_print_enter!`] only in debug builds or tests of the calling crate.
+/// Otherwise return [`ExitGuard::none`].
+#[cfg(not(feature = "always"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_print_enter_debug {
+    (
+        $($args:tt)*
+    ) => {{
+        #[cfg(any(debug_assertions,test))]
+        let guard = $crate::__si_print_enter!($($args)*);
+        #[cfg(not(any(debug_assertions,test)))]
+        let guard = $crate::printers::ExitGuard::none();
+        guard
+    }};
+}
+
+/// With feature `always`, [`__si_print_enter!`] in all builds.
+#[cfg(feature = "always")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_print_enter_debug {
+    (
+        $($args:tt)*
+    ) => {
+        $crate::__si_print_enter!($($args)*)
+    };
+}
+
+/// [`format_args!`] that also accepts no arguments, like [`println!`].
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __si_format_args {
+    () => {
+        format_args!("")
+    };
+    (
+        $($args:tt)+
+    ) => {
+        format_args!($($args)+)
+    };
+}
+
 //
 // `p`rintln
 //
@@ -100,12 +1329,7 @@ macro_rules! p {
     (
         $($args:tt)*
     ) => {{
-        // for consistency with other macros, invoke setting the
-        // "original" stack depth via `so`
-        $crate::stack::so();
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Plain, None, $($args)*);
     }}
 }
 pub use p;
@@ -145,10 +1369,7 @@ macro_rules! po {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}", $crate::stack::so());
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Offset, None, $($args)*);
     }}
 }
 pub use po;
@@ -188,10 +1409,7 @@ macro_rules! pn {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}", $crate::stack::sn());
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Enter, None, $($args)*);
     }}
 }
 pub use pn;
@@ -231,10 +1449,7 @@ macro_rules! px {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}", $crate::stack::sx());
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Exit, None, $($args)*);
     }}
 }
 pub use px;
@@ -275,10 +1490,7 @@ macro_rules! pñ {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}", $crate::stack::sñ());
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, EnterExit, None, $($args)*);
     }}
 }
 pub use pñ;
@@ -319,10 +1531,7 @@ macro_rules! pfo {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name!());
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Offset, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use pfo;
@@ -362,10 +1571,7 @@ macro_rules! pfn {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name!());
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Enter, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use pfn;
@@ -405,10 +1611,7 @@ macro_rules! pfx {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name!());
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Exit, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use pfx;
@@ -450,10 +1653,7 @@ macro_rules! pfñ {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name!());
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, EnterExit, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use pfñ;
@@ -499,10 +1699,7 @@ macro_rules! pf1o {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name_plus!(1));
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Offset, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use pf1o;
@@ -544,10 +1741,7 @@ macro_rules! pf1n {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name_plus!(1));
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Enter, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use pf1n;
@@ -589,10 +1783,7 @@ macro_rules! pf1x {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name_plus!(1));
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Exit, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use pf1x;
@@ -635,10 +1826,7 @@ macro_rules! pf1ñ {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name_plus!(1));
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, EnterExit, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use pf1ñ;
@@ -684,10 +1872,7 @@ macro_rules! pf2o {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name_plus!(2));
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Offset, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use pf2o;
@@ -729,10 +1914,7 @@ macro_rules! pf2n {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name_plus!(2));
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Enter, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use pf2n;
@@ -774,10 +1956,7 @@ macro_rules! pf2x {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name_plus!(2));
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, Exit, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use pf2x;
@@ -820,10 +1999,7 @@ macro_rules! pf2ñ {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        print!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name_plus!(2));
-        println!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stdout, EnterExit, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use pf2ñ;
@@ -863,12 +2039,7 @@ macro_rules! e {
     (
         $($args:tt)*
     ) => {{
-        // for consistency with other macros, invoke setting the
-        // "original" stack depth via `so`
-        $crate::stack::so();
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Plain, None, $($args)*);
     }}
 }
 pub use e;
@@ -908,10 +2079,7 @@ macro_rules! eo {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}", $crate::stack::so());
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Offset, None, $($args)*);
     }}
 }
 pub use eo;
@@ -955,10 +2123,7 @@ macro_rules! en {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}", $crate::stack::sn());
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Enter, None, $($args)*);
     }}
 }
 pub use en;
@@ -998,10 +2163,7 @@ macro_rules! ex {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}", $crate::stack::sx());
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Exit, None, $($args)*);
     }}
 }
 pub use ex;
@@ -1042,10 +2204,7 @@ macro_rules! eñ {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}", $crate::stack::sñ());
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, EnterExit, None, $($args)*);
     }}
 }
 pub use eñ;
@@ -1090,10 +2249,7 @@ macro_rules! efo {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name!());
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Offset, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use efo;
@@ -1133,10 +2289,7 @@ macro_rules! efn {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name!());
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Enter, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use efn;
@@ -1176,10 +2329,7 @@ macro_rules! efx {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name!());
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Exit, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use efx;
@@ -1221,10 +2371,7 @@ macro_rules! efñ {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name!());
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, EnterExit, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use efñ;
@@ -1270,10 +2417,7 @@ macro_rules! ef1o {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name_plus!(1));
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Offset, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use ef1o;
@@ -1315,10 +2459,7 @@ macro_rules! ef1n {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name_plus!(1));
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Enter, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use ef1n;
@@ -1360,10 +2501,7 @@ macro_rules! ef1x {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name_plus!(1));
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Exit, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use ef1x;
@@ -1406,10 +2544,7 @@ macro_rules! ef1ñ {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name_plus!(1));
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, EnterExit, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use ef1ñ;
@@ -1455,10 +2590,7 @@ macro_rules! ef2o {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name_plus!(2));
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Offset, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use ef2o;
@@ -1500,10 +2632,7 @@ macro_rules! ef2n {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name_plus!(2));
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Enter, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use ef2n;
@@ -1545,10 +2674,7 @@ macro_rules! ef2x {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name_plus!(2));
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, Exit, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use ef2x;
@@ -1591,10 +2717,7 @@ macro_rules! ef2ñ {
     (
         $($args:tt)*
     ) => {{
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        eprint!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name_plus!(2));
-        eprintln!($($args)*);
-        drop(lock);
+        $crate::__si_print!(Stderr, EnterExit, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use ef2ñ;
@@ -1636,12 +2759,7 @@ macro_rules! dp {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Plain, None, $($args)*);
     }}
 }
 pub use dp;
@@ -1683,14 +2801,7 @@ macro_rules! dpo {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}", $crate::stack::so());
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Offset, None, $($args)*);
     }}
 }
 pub use dpo;
@@ -1732,14 +2843,7 @@ macro_rules! dpn {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}", $crate::stack::sn());
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Enter, None, $($args)*);
     }}
 }
 pub use dpn;
@@ -1781,14 +2885,7 @@ macro_rules! dpx {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}", $crate::stack::sx());
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Exit, None, $($args)*);
     }}
 }
 pub use dpx;
@@ -1831,14 +2928,7 @@ macro_rules! dpñ {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}", $crate::stack::sñ());
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, EnterExit, None, $($args)*);
     }}
 }
 pub use dpñ;
@@ -1881,14 +2971,7 @@ macro_rules! dpfo {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name!());
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Offset, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use dpfo;
@@ -1934,14 +3017,7 @@ macro_rules! dpfn {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name!());
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Enter, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use dpfn;
@@ -1983,14 +3059,7 @@ macro_rules! dpfx {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name!());
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Exit, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use dpfx;
@@ -2032,16 +3101,9 @@ pub use dpfx;
 #[macro_export]
 macro_rules! dpfñ {
     (
-        $($args:tt)*
-    ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name!());
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $($args:tt)*
+    ) => {{
+        $crate::__si_print_debug!(Stdout, EnterExit, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use dpfñ;
@@ -2089,14 +3151,7 @@ macro_rules! dpf1o {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name_plus!(1));
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Offset, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use dpf1o;
@@ -2140,14 +3195,7 @@ macro_rules! dpf1n {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name_plus!(1));
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Enter, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use dpf1n;
@@ -2191,14 +3239,7 @@ macro_rules! dpf1x {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name_plus!(1));
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Exit, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use dpf1x;
@@ -2243,14 +3284,7 @@ macro_rules! dpf1ñ {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name_plus!(1));
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, EnterExit, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use dpf1ñ;
@@ -2298,14 +3332,7 @@ macro_rules! dpf2o {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name_plus!(2));
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Offset, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use dpf2o;
@@ -2349,14 +3376,7 @@ macro_rules! dpf2n {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name_plus!(2));
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Enter, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use dpf2n;
@@ -2400,14 +3420,7 @@ macro_rules! dpf2x {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name_plus!(2));
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, Exit, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use dpf2x;
@@ -2452,14 +3465,7 @@ macro_rules! dpf2ñ {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        print!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name_plus!(2));
-        #[cfg(any(debug_assertions,test))]
-        println!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stdout, EnterExit, Some($crate::function_name::function_name_plus!(2)), $($args)*);
     }}
 }
 pub use dpf2ñ;
@@ -2501,12 +3507,7 @@ macro_rules! de {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Plain, None, $($args)*);
     }}
 }
 pub use de;
@@ -2548,14 +3549,7 @@ macro_rules! deo {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}", $crate::stack::so());
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Offset, None, $($args)*);
     }}
 }
 pub use deo;
@@ -2597,14 +3591,7 @@ macro_rules! den {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}", $crate::stack::sn());
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Enter, None, $($args)*);
     }}
 }
 pub use den;
@@ -2646,14 +3633,7 @@ macro_rules! dex {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}", $crate::stack::sx());
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Exit, None, $($args)*);
     }}
 }
 pub use dex;
@@ -2696,14 +3676,7 @@ macro_rules! deñ {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}", $crate::stack::sñ());
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, EnterExit, None, $($args)*);
     }}
 }
 pub use deñ;
@@ -2750,14 +3723,7 @@ macro_rules! defo {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name!());
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Offset, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use defo;
@@ -2799,14 +3765,7 @@ macro_rules! defn {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name!());
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Enter, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use defn;
@@ -2848,14 +3807,7 @@ macro_rules! defx {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name!());
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Exit, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use defx;
@@ -2899,14 +3851,7 @@ macro_rules! defñ {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name!());
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, EnterExit, Some($crate::function_name::function_name!()), $($args)*);
     }}
 }
 pub use defñ;
@@ -2954,14 +3899,7 @@ macro_rules! def1o {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name_plus!(1));
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Offset, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use def1o;
@@ -3005,14 +3943,7 @@ macro_rules! def1n {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name_plus!(1));
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Enter, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use def1n;
@@ -3056,14 +3987,7 @@ macro_rules! def1x {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name_plus!(1));
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, Exit, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use def1x;
@@ -3108,14 +4032,7 @@ macro_rules! def1ñ {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name_plus!(1));
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_debug!(Stderr, EnterExit, Some($crate::function_name::function_name_plus!(1)), $($args)*);
     }}
 }
 pub use def1ñ;
@@ -3131,15 +4048,200 @@ pub use def1ñ;
 /// Use to [`eprintln!`] in a [function].
 ///
 /// ```rust
-/// use si_trace_print::{defn, defx};
-/// use si_trace_print::def2o;
-/// fn func1() {
-///     def2o!("hello");
+/// use si_trace_print::{defn, defx};
+/// use si_trace_print::def2o;
+/// fn func1() {
+///     def2o!("hello");
+/// }
+/// fn main() {
+///     defn!();
+///     func1();
+///     defx!();
+/// }
+/// ```
+///
+/// prints
+///
+/// ```text
+/// →main:
+///      si_trace_print::main::func1: hello
+/// ←main:
+/// ```
+///
+/// Uses [`so()`].
+///
+/// For debug builds.
+///
+/// [`eprintln!`]: eprintln!
+/// [function]: crate::function_name::function_name_plus
+/// [`so()`]: crate::stack::so
+#[macro_export]
+macro_rules! def2o {
+    (
+        $($args:tt)*
+    ) => {{
+        $crate::__si_print_debug!(Stderr, Offset, Some($crate::function_name::function_name_plus!(2)), $($args)*);
+    }}
+}
+pub use def2o;
+
+/// **d**ebug **e**println! in a **f**unction plus **two** namespace levels
+/// when e**n**tering.
+///
+/// To signify entering a function.
+/// Use this as the first [`eprintln!`] in a [function].
+///
+/// ```rust
+/// use si_trace_print::{defn, defx};
+/// use si_trace_print::def2n;
+/// fn func1() {
+///     def2n!("hello");
+/// }
+/// fn main() {
+///     defn!();
+///     func1();
+///     defx!();
+/// }
+/// ```
+///
+/// prints
+///
+/// ```text
+/// →main:
+///     →si_trace_print::main::func1: hello
+/// ←main:
+/// ```
+///
+/// Uses [`sn()`].
+///
+/// For debug builds.
+///
+/// [`eprintln!`]: eprintln!
+/// [function]: crate::function_name::function_name_plus
+/// [`sn()`]: crate::stack::sn
+#[macro_export]
+macro_rules! def2n {
+    (
+        $($args:tt)*
+    ) => {{
+        $crate::__si_print_debug!(Stderr, Enter, Some($crate::function_name::function_name_plus!(2)), $($args)*);
+    }}
+}
+pub use def2n;
+
+/// **d**ebug **e**println! in a **f**unction plus **two** namespace levels
+/// when e**x**iting.
+///
+/// To signify exiting a function.
+/// Use this as the last [`eprintln!`] in a [function].
+///
+/// ```rust
+/// use si_trace_print::{defn, defx};
+/// use si_trace_print::def2x;
+/// fn func1() {
+///     def2x!("hello");
+/// }
+/// fn main() {
+///     defn!();
+///     func1();
+///     defx!();
+/// }
+/// ```
+///
+/// prints
+///
+/// ```text
+/// →main:
+///     ←si_trace_print::main::func1: hello
+/// ←main:
+/// ```
+///
+/// Uses [`sx()`].
+///
+/// For debug builds.
+///
+/// [`eprintln!`]: eprintln!
+/// [function]: crate::function_name::function_name_plus
+/// [`sx()`]: crate::stack::sx
+#[macro_export]
+macro_rules! def2x {
+    (
+        $($args:tt)*
+    ) => {{
+        $crate::__si_print_debug!(Stderr, Exit, Some($crate::function_name::function_name_plus!(2)), $($args)*);
+    }}
+}
+pub use def2x;
+
+/// **d**ebug **e**println! in a **f**unction plus **two** namespace levels
+/// when e**n**tering and e**x**iting.
+///
+/// To signify entering and exiting a function.
+/// Use this as the only [`eprintln!`] in a [function].
+/// Most suited for short functions.
+///
+/// ```rust
+/// use si_trace_print::{defn, defx};
+/// use si_trace_print::def2ñ;
+/// fn func1() {
+///     def2ñ!("hello");
+/// }
+/// fn main() {
+///     defn!();
+///     func1();
+///     defx!();
+/// }
+/// ```
+///
+/// prints
+///
+/// ```text
+/// →main:
+///     ↔si_trace_print::main::func1: hello
+/// ←main:
+/// ```
+///
+/// Uses [`sñ()`].
+///
+/// For debug builds.
+///
+/// [`eprintln!`]: eprintln!
+/// [function]: crate::function_name::function_name_plus
+/// [`sñ()`]: crate::stack::sñ
+#[macro_export]
+macro_rules! def2ñ {
+    (
+        $($args:tt)*
+    ) => {{
+        $crate::__si_print_debug!(Stderr, EnterExit, Some($crate::function_name::function_name_plus!(2)), $($args)*);
+    }}
+}
+pub use def2ñ;
+
+//
+// `g`uard macros that print when entering and print again when the returned
+// guard is dropped
+//
+
+/// **p**rintln! in a **f**unction when entering and return a **g**uard that
+/// prints when exiting.
+///
+/// To signify entering a function and, when the returned [`ExitGuard`] is
+/// dropped, exiting the function.
+/// The exit line is printed after an early `return`, a `?`, or a panic.
+///
+/// ```rust
+/// use si_trace_print::{pfg, pfo};
+/// fn func1() -> Result<(), ()> {
+///     let _guard = pfg!("hello");
+///     pfo!("return early");
+///     Err(())?;
+///     pfo!("never printed");
+///     Ok(())
 /// }
 /// fn main() {
-///     defn!();
-///     func1();
-///     defx!();
+///     let _guard = pfg!();
+///     _ = func1();
 /// }
 /// ```
 ///
@@ -3147,50 +4249,48 @@ pub use def1ñ;
 ///
 /// ```text
 /// →main:
-///      si_trace_print::main::func1: hello
+///     →func1: hello
+///      func1: return early
+///     ←func1:
 /// ←main:
 /// ```
 ///
-/// Uses [`so()`].
+/// If dropped while panicking then the exit line is `←func1: [panicking]`.
 ///
-/// For debug builds.
+/// Uses [`sn()`] and [`sx()`].
 ///
-/// [`eprintln!`]: eprintln!
-/// [function]: crate::function_name::function_name_plus
-/// [`so()`]: crate::stack::so
+/// [`ExitGuard`]: crate::printers::ExitGuard
+/// [`sn()`]: crate::stack::sn
+/// [`sx()`]: crate::stack::sx
 #[macro_export]
-macro_rules! def2o {
+macro_rules! pfg {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::so(), $crate::function_name::function_name_plus!(2));
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_enter!(Stdout, Enter, Some($crate::function_name::function_name!()), $($args)*)
     }}
 }
-pub use def2o;
+pub use pfg;
 
-/// **d**ebug **e**println! in a **f**unction plus **two** namespace levels
-/// when e**n**tering.
+/// **e**println! in a **f**unction when entering and return a **g**uard that
+/// prints when exiting.
 ///
-/// To signify entering a function.
-/// Use this as the first [`eprintln!`] in a [function].
+/// To signify entering a function and, when the returned [`ExitGuard`] is
+/// dropped, exiting the function.
+/// The exit line is printed after an early `return`, a `?`, or a panic.
 ///
 /// ```rust
-/// use si_trace_print::{defn, defx};
-/// use si_trace_print::def2n;
-/// fn func1() {
-///     def2n!("hello");
+/// use si_trace_print::{efg, efo};
+/// fn func1() -> Result<(), ()> {
+///     let _guard = efg!("hello");
+///     efo!("return early");
+///     Err(())?;
+///     efo!("never printed");
+///     Ok(())
 /// }
 /// fn main() {
-///     defn!();
-///     func1();
-///     defx!();
+///     let _guard = efg!();
+///     _ = func1();
 /// }
 /// ```
 ///
@@ -3198,50 +4298,48 @@ pub use def2o;
 ///
 /// ```text
 /// →main:
-///     →si_trace_print::main::func1: hello
+///     →func1: hello
+///      func1: return early
+///     ←func1:
 /// ←main:
 /// ```
 ///
-/// Uses [`sn()`].
+/// If dropped while panicking then the exit line is `←func1: [panicking]`.
 ///
-/// For debug builds.
+/// Uses [`sn()`] and [`sx()`].
 ///
-/// [`eprintln!`]: eprintln!
-/// [function]: crate::function_name::function_name_plus
+/// [`ExitGuard`]: crate::printers::ExitGuard
 /// [`sn()`]: crate::stack::sn
+/// [`sx()`]: crate::stack::sx
 #[macro_export]
-macro_rules! def2n {
+macro_rules! efg {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::sn(), $crate::function_name::function_name_plus!(2));
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_enter!(Stderr, Enter, Some($crate::function_name::function_name!()), $($args)*)
     }}
 }
-pub use def2n;
+pub use efg;
 
-/// **d**ebug **e**println! in a **f**unction plus **two** namespace levels
-/// when e**x**iting.
+/// **d**ebug **p**rintln! in a **f**unction when entering and return a
+/// **g**uard that prints when exiting.
 ///
-/// To signify exiting a function.
-/// Use this as the last [`eprintln!`] in a [function].
+/// To signify entering a function and, when the returned [`ExitGuard`] is
+/// dropped, exiting the function.
+/// The exit line is printed after an early `return`, a `?`, or a panic.
 ///
 /// ```rust
-/// use si_trace_print::{defn, defx};
-/// use si_trace_print::def2x;
-/// fn func1() {
-///     def2x!("hello");
+/// use si_trace_print::{dpfg, dpfo};
+/// fn func1() -> Result<(), ()> {
+///     let _guard = dpfg!("hello");
+///     dpfo!("return early");
+///     Err(())?;
+///     dpfo!("never printed");
+///     Ok(())
 /// }
 /// fn main() {
-///     defn!();
-///     func1();
-///     defx!();
+///     let _guard = dpfg!();
+///     _ = func1();
 /// }
 /// ```
 ///
@@ -3249,51 +4347,50 @@ pub use def2n;
 ///
 /// ```text
 /// →main:
-///     ←si_trace_print::main::func1: hello
+///     →func1: hello
+///      func1: return early
+///     ←func1:
 /// ←main:
 /// ```
 ///
-/// Uses [`sx()`].
+/// If dropped while panicking then the exit line is `←func1: [panicking]`.
 ///
-/// For debug builds.
+/// Uses [`sn()`] and [`sx()`].
 ///
-/// [`eprintln!`]: eprintln!
-/// [function]: crate::function_name::function_name_plus
+/// For debug builds. In non-debug builds the returned guard does nothing.
+///
+/// [`ExitGuard`]: crate::printers::ExitGuard
+/// [`sn()`]: crate::stack::sn
 /// [`sx()`]: crate::stack::sx
 #[macro_export]
-macro_rules! def2x {
+macro_rules! dpfg {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::sx(), $crate::function_name::function_name_plus!(2));
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_enter_debug!(Stdout, Enter, Some($crate::function_name::function_name!()), $($args)*)
     }}
 }
-pub use def2x;
+pub use dpfg;
 
-/// **d**ebug **e**println! in a **f**unction plus **two** namespace levels
-/// when e**n**tering and e**x**iting.
+/// **d**ebug **e**println! in a **f**unction when entering and return a
+/// **g**uard that prints when exiting.
 ///
-/// To signify entering and exiting a function.
-/// Use this as the only [`eprintln!`] in a [function].
-/// Most suited for short functions.
+/// To signify entering a function and, when the returned [`ExitGuard`] is
+/// dropped, exiting the function.
+/// The exit line is printed after an early `return`, a `?`, or a panic.
 ///
 /// ```rust
-/// use si_trace_print::{defn, defx};
-/// use si_trace_print::def2ñ;
-/// fn func1() {
-///     def2ñ!("hello");
+/// use si_trace_print::{defg, defo};
+/// fn func1() -> Result<(), ()> {
+///     let _guard = defg!("hello");
+///     defo!("return early");
+///     Err(())?;
+///     defo!("never printed");
+///     Ok(())
 /// }
 /// fn main() {
-///     defn!();
-///     func1();
-///     defx!();
+///     let _guard = defg!();
+///     _ = func1();
 /// }
 /// ```
 ///
@@ -3301,33 +4398,30 @@ pub use def2x;
 ///
 /// ```text
 /// →main:
-///     ↔si_trace_print::main::func1: hello
+///     →func1: hello
+///      func1: return early
+///     ←func1:
 /// ←main:
 /// ```
 ///
-/// Uses [`sñ()`].
+/// If dropped while panicking then the exit line is `←func1: [panicking]`.
 ///
-/// For debug builds.
+/// Uses [`sn()`] and [`sx()`].
 ///
-/// [`eprintln!`]: eprintln!
-/// [function]: crate::function_name::function_name_plus
-/// [`sñ()`]: crate::stack::sñ
+/// For debug builds. In non-debug builds the returned guard does nothing.
+///
+/// [`ExitGuard`]: crate::printers::ExitGuard
+/// [`sn()`]: crate::stack::sn
+/// [`sx()`]: crate::stack::sx
 #[macro_export]
-macro_rules! def2ñ {
+macro_rules! defg {
     (
         $($args:tt)*
     ) => {{
-        #[cfg(any(debug_assertions,test))]
-        let lock = $crate::printers::GLOBAL_LOCK_PRINTER.lock().unwrap();
-        #[cfg(any(debug_assertions,test))]
-        eprint!("{}{}: ", $crate::stack::sñ(), $crate::function_name::function_name_plus!(2));
-        #[cfg(any(debug_assertions,test))]
-        eprintln!($($args)*);
-        #[cfg(any(debug_assertions,test))]
-        drop(lock);
+        $crate::__si_print_enter_debug!(Stderr, Enter, Some($crate::function_name::function_name!()), $($args)*)
     }}
 }
-pub use def2ñ;
+pub use defg;
 
 //
 // tests
@@ -3335,9 +4429,807 @@ pub use def2ñ;
 
 #[cfg(test)]
 mod tests {
-    use crate::stack::stack_offset_set;
+    use super::{
+        capture, file_short, reset_writer, set_elapsed, set_enabled, set_filter, set_location, set_prefix,
+        set_thread_columns, set_writer, thread_column, thread_number, Filter, Location, Prefix, Timestamp,
+    };
+    use crate::color::{set_color, Color};
+    use crate::stack::{set_depth_mode, set_style, stack_offset_set, DepthMode, Style};
+    use crate::test_settings_lock;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
     use std::thread;
-    use std::time::Duration;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// A `Write` sink shared with the test function.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn string(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_writer() {
+        let _lock = test_settings_lock();
+        let buffer = Buffer::default();
+        set_writer(Box::new(buffer.clone()));
+        p!("test_set_writer p!");
+        pfn!("test_set_writer pfn!");
+        efo!("test_set_writer efo!");
+        dpx!("test_set_writer dpx!");
+        def1ñ!("test_set_writer def1ñ!");
+        assert!(reset_writer().is_some());
+        assert!(reset_writer().is_none());
+        e!("test_set_writer after reset_writer");
+
+        // other tests may print to the buffer at the same time
+        let printed = buffer.string();
+        assert!(printed.lines().any(|line| line == "test_set_writer p!"));
+        assert!(printed.contains("→test_set_writer: test_set_writer pfn!\n"));
+        assert!(printed.contains(" test_set_writer: test_set_writer efo!\n"));
+        assert!(printed.contains("←test_set_writer dpx!\n"));
+        assert!(printed.contains("↔tests::test_set_writer: test_set_writer def1ñ!\n"));
+        assert!(!printed.contains("after reset_writer"));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    #[cfg_attr(not(debug_assertions), ignore = "stack offsets differ when functions are inlined")]
+    fn test_capture() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defn!("({})", 1);
+            func2();
+            dpo!("func1 stdout");
+            defx!();
+        }
+        fn func2() {
+            deñ!("func2");
+        }
+        let printed = thread::spawn(|| capture(func1)).join().unwrap();
+        assert_eq!(printed, "→func1: (1)\n    ↔func2\n func1 stdout\n←func1: \n");
+    }
+
+    #[test]
+    #[cfg(feature = "off")]
+    fn test_feature_off() {
+        fn not_evaluated() -> usize {
+            unreachable!("feature off must not evaluate macro arguments")
+        }
+        let printed = capture(|| {
+            let _guard = pfg!("{}", not_evaluated());
+            defn!("{}", not_evaluated());
+            po!("{}", not_evaluated());
+            e!("{}", not_evaluated());
+        });
+        assert_eq!(printed, "");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_guard() {
+        let _lock = test_settings_lock();
+        fn func1(early: bool) -> Result<(), ()> {
+            let _guard = defg!("({})", early);
+            if early {
+                Err(())?;
+            }
+            defo!("late");
+            Ok(())
+        }
+        fn func2() {
+            let _guard = efg!();
+            _ = func1(true);
+            _ = func1(false);
+        }
+        let printed = thread::spawn(|| capture(func2)).join().unwrap();
+        assert_eq!(
+            printed,
+            "→func2: \n    →func1: (true)\n    ←func1: \n    →func1: (false)\n     func1: late\n    ←func1: \n←func2: \n"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_depth_mode() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defn!();
+            // frames between enter macros do not change the indentation
+            call(func2);
+            func3();
+            defo!("o");
+            defx!();
+        }
+        fn func2() {
+            defn!();
+            defo!("o");
+            defñ!("ñ");
+            defx!();
+        }
+        fn call(f: fn()) {
+            f();
+        }
+        fn func3() {
+            let _guard = defg!();
+            defo!("o");
+        }
+        set_depth_mode(DepthMode::Counter);
+        let printed = thread::spawn(|| capture(func1)).join().unwrap();
+        assert_eq!(set_depth_mode(DepthMode::Backtrace), DepthMode::Counter);
+        assert_eq!(
+            printed,
+            "→func1: \n    →func2: \n     func2: o\n        ↔func2: ñ\n    ←func2: \n    →func3: \n     func3: o\n    ←func3: \n func1: o\n←func1: \n"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_depth_mode_crates() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defn!();
+            call(func2);
+            defx!();
+        }
+        fn func2() {
+            defñ!();
+            let _guard = defg!();
+        }
+        fn call(f: fn()) {
+            f();
+        }
+        set_depth_mode(DepthMode::Crates(&[
+            "si_trace_print::printers::tests::test_set_depth_mode_crates",
+        ]));
+        // the outermost counted frame is the thread closure
+        let printed = thread::spawn(|| capture(func1)).join().unwrap();
+        set_depth_mode(DepthMode::Backtrace);
+        assert_eq!(
+            printed,
+            "    →func1: \n            ↔func2: \n            →func2: \n            ←func2: \n    ←func1: \n"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    #[cfg_attr(not(debug_assertions), ignore = "`DepthMode::Inlined` requires debug info")]
+    fn test_set_depth_mode_inlined() {
+        let _lock = test_settings_lock();
+        #[inline(never)]
+        fn func1() {
+            defn!();
+            func2();
+            defx!();
+        }
+        #[inline(always)]
+        fn func2() {
+            defñ!();
+        }
+        set_depth_mode(DepthMode::Inlined);
+        let printed = thread::spawn(|| capture(func1)).join().unwrap();
+        set_depth_mode(DepthMode::Backtrace);
+        assert_eq!(printed, "→func1: \n    ↔func2: \n←func1: \n");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    #[cfg_attr(not(debug_assertions), ignore = "stack offsets differ when functions are inlined")]
+    fn test_set_style_tree() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defn!("({})", 3);
+            func2();
+            defo!("late");
+            defx!();
+        }
+        fn func2() {
+            let _guard = defg!();
+            defo!("doing stuff");
+            func3();
+        }
+        fn func3() {
+            defñ!();
+        }
+        set_style(Style::Tree);
+        let printed = thread::spawn(|| capture(func1)).join().unwrap();
+        assert_eq!(set_style(Style::Indent), Style::Tree);
+        assert_eq!(
+            printed,
+            "\
+├─→func1: (3)
+│   ├─→func2: 
+│   │  func2: doing stuff
+│   │   ├─↔func3: 
+│   └─←func2: 
+│  func1: late
+└─←func1: 
+"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_style_tree_exiting() {
+        let _lock = test_settings_lock();
+        struct PrintOnDrop;
+        impl Drop for PrintOnDrop {
+            fn drop(&mut self) {
+                pfo!("test_set_style_tree_exiting");
+            }
+        }
+        thread_local! {
+            static PRINT_ON_DROP: PrintOnDrop = const { PrintOnDrop };
+        }
+        fn func1() {
+            // thread-local destructors run in reverse order, so the tree scopes
+            // are destroyed before `PRINT_ON_DROP` prints
+            PRINT_ON_DROP.with(|_| ());
+            pfo!("test_set_style_tree_exiting");
+        }
+        let buffer = Buffer::default();
+        set_writer(Box::new(buffer.clone()));
+        set_style(Style::Tree);
+        thread::spawn(func1).join().unwrap();
+        assert_eq!(set_style(Style::Indent), Style::Tree);
+        reset_writer();
+        // other tests may print to the buffer at the same time
+        let printed = buffer.string();
+        let lines: Vec<&str> = printed
+            .lines()
+            .filter(|line| line.ends_with("test_set_style_tree_exiting"))
+            .collect();
+        assert_eq!(lines.len(), 2, "{:?}", printed);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_guard_panic() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            let _guard = dpfg!();
+            panic!("test_guard_panic");
+        }
+        let printed = thread::spawn(|| {
+            capture(|| {
+                let _guard = pfg!();
+                assert!(std::panic::catch_unwind(func1).is_err());
+            })
+        })
+        .join()
+        .unwrap();
+        // `catch_unwind` adds stack frames so only compare relative indentation
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines.len(), 4, "{:?}", lines);
+        assert_eq!(lines[0], "→{{closure}}: ");
+        assert_eq!(lines[1].trim_start(), "→func1: ");
+        assert_eq!(lines[2].trim_start(), "←func1: [panicking]");
+        assert_eq!(lines[1].find('→'), lines[2].find('←'));
+        assert_eq!(lines[3], "←{{closure}}: ");
+    }
+
+    /// Panic on message `test_guard_panic_poisoned`.
+    fn hook_panic(record: &crate::record::Record) {
+        if record.message.to_string() == "test_guard_panic_poisoned" {
+            panic!("hook_panic");
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_guard_panic_poisoned() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            let _guard = dpfg!();
+            // the hook panics while holding the global lock
+            dpfo!("test_guard_panic_poisoned");
+        }
+        crate::record::set_hook(hook_panic);
+        let printed = thread::spawn(|| {
+            capture(|| {
+                assert!(std::panic::catch_unwind(func1).is_err());
+            })
+        })
+        .join();
+        crate::record::reset_hook();
+        let printed = printed.unwrap();
+        let lines: Vec<&str> = printed.lines().map(str::trim_start).collect();
+        assert_eq!(lines, ["→func1: ", "←func1: [panicking]"]);
+    }
+
+    #[test]
+    fn test_thread_number() {
+        let number = thread_number();
+        assert_eq!(number, thread_number());
+        let number_other = thread::spawn(thread_number).join().unwrap();
+        assert_ne!(number, number_other);
+        assert_eq!(number, thread_number());
+    }
+
+    #[test]
+    fn test_thread_column_reused() {
+        let numbers: std::collections::HashSet<usize> =
+            (0..100).map(|_| thread::spawn(thread_number).join().unwrap()).collect();
+        assert_eq!(numbers.len(), 100);
+        // other tests may run threads concurrently, so only expect most
+        // columns of exited threads to be assigned again
+        let columns: std::collections::HashSet<usize> =
+            (0..100).map(|_| thread::spawn(thread_column).join().unwrap()).collect();
+        assert!(columns.len() < 50, "{:?}", columns);
+    }
+
+    /// Prints twice when dropped.
+    struct PrintOnDrop;
+
+    impl Drop for PrintOnDrop {
+        fn drop(&mut self) {
+            pfo!("test_thread_column_exiting");
+            pfo!("test_thread_column_exiting");
+        }
+    }
+
+    thread_local! {
+        static PRINT_ON_DROP: PrintOnDrop = const { PrintOnDrop };
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_thread_column_exiting() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            // thread-local destructors run in reverse order, so the column is
+            // freed before `PRINT_ON_DROP` prints
+            PRINT_ON_DROP.with(|_| ());
+            thread_column();
+        }
+        let buffer = Buffer::default();
+        set_writer(Box::new(buffer.clone()));
+        set_thread_columns(Some(1));
+        thread::spawn(func1).join().unwrap();
+        set_thread_columns(None);
+        reset_writer();
+        // other tests may print to the buffer at the same time
+        let printed = buffer.string();
+        let lines: Vec<&str> = printed
+            .lines()
+            .filter(|line| line.ends_with("test_thread_column_exiting"))
+            .collect();
+        assert_eq!(lines.len(), 2, "{:?}", printed);
+        assert_eq!(lines[0], lines[1]);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_thread_columns() {
+        let _lock = test_settings_lock();
+        fn func1() -> (usize, String) {
+            (thread_column(), capture(func2))
+        }
+        fn func2() {
+            defñ!();
+        }
+        set_thread_columns(Some(10));
+        let (number, printed) = thread::spawn(func1).join().unwrap();
+        set_color(Color::Always);
+        let (number_other, printed_other) = thread::spawn(func1).join().unwrap();
+        assert_eq!(set_color(Color::Auto), Color::Always);
+        assert_eq!(set_thread_columns(None), Some(10));
+        // a capture is not colored by `Color::Auto`
+        assert_eq!(printed, format!("{}↔func2: \n", " ".repeat(10 * number)));
+        assert_eq!(
+            printed_other,
+            format!(
+                "\x1b[3{}m{}↔func2: \x1b[0m\n",
+                number_other % 6 + 1,
+                " ".repeat(10 * number_other)
+            )
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    #[cfg_attr(not(debug_assertions), ignore = "stack offsets differ when functions are inlined")]
+    fn test_set_color() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defn!("{}", 1);
+            func2();
+            defx!();
+        }
+        fn func2() {
+            defo!("hello");
+            de!("plain");
+        }
+        set_color(Color::Always);
+        let printed = thread::spawn(|| capture(func1)).join().unwrap();
+        set_color(Color::Never);
+        let printed_never = thread::spawn(|| capture(func1)).join().unwrap();
+        assert_eq!(set_color(Color::Auto), Color::Never);
+        assert_eq!(
+            printed,
+            concat!(
+                "\x1b[0m\x1b[32m→\x1b[0m\x1b[2mfunc1\x1b[0m\x1b[32m: 1\x1b[0m\n",
+                "\x1b[31m    \x1b[0m\x1b[34m \x1b[0m\x1b[2mfunc2\x1b[0m\x1b[34m: hello\x1b[0m\n",
+                "plain\n",
+                "\x1b[0m\x1b[33m←\x1b[0m\x1b[2mfunc1\x1b[0m\x1b[33m: \x1b[0m\n",
+            )
+        );
+        assert_eq!(printed_never, "→func1: 1\n     func2: hello\nplain\n←func1: \n");
+    }
+
+    #[test]
+    fn test_file_short() {
+        assert_eq!(file_short("src/lib.rs"), "src/lib.rs");
+        assert_eq!(file_short("crates/parser/src/lexer.rs"), "src/lexer.rs");
+        assert_eq!(file_short("crates/parser/src/tests/lexer.rs"), "src/tests/lexer.rs");
+        assert_eq!(
+            file_short("/home/user/.cargo/registry/src/index.crates.io-6f17d22bba15001f/foo-1.0.0/src/lib.rs"),
+            "src/lib.rs"
+        );
+        assert_eq!(file_short("crates\\parser\\src\\lexer.rs"), "src\\lexer.rs");
+        assert_eq!(file_short("crates/parser/tests/lexer.rs"), "tests/lexer.rs");
+        assert_eq!(
+            file_short("crates/parser/examples/tests/lexer.rs"),
+            "examples/tests/lexer.rs"
+        );
+        assert_eq!(file_short("build.rs"), "build.rs");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_location() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defn!();
+            deo!("hello");
+        }
+        set_location(Location::Short);
+        let line = line!() - 4;
+        let printed = thread::spawn(|| capture(func1)).join().unwrap();
+        set_location(Location::Full);
+        let printed_full = thread::spawn(|| capture(func1)).join().unwrap();
+        assert_eq!(set_location(Location::Off), Location::Full);
+        assert_eq!(
+            printed,
+            format!(
+                "→func1@src/printers.rs:{}: \n src/printers.rs:{}: hello\n",
+                line,
+                line + 1
+            )
+        );
+        assert_eq!(
+            printed_full,
+            format!("→func1@{}:{}: \n {}:{}: hello\n", file!(), line, file!(), line + 1)
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let at = |secs: u64, micros: u64| {
+            Timestamp(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_micros(micros)).to_string()
+        };
+        assert_eq!(at(0, 0), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(at(951_782_400, 1), "2000-02-29T00:00:00.000001Z");
+        assert_eq!(at(1_700_000_000, 123_456), "2023-11-14T22:13:20.123456Z");
+        assert_eq!(at(1_709_251_199, 999_999), "2024-02-29T23:59:59.999999Z");
+        assert_eq!(at(4_102_444_800, 0), "2100-01-01T00:00:00.000000Z");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_prefix() {
+        let _lock = test_settings_lock();
+        set_prefix(Prefix::NONE.with_sequence(true).with_thread(true));
+        fn func1() {
+            defn!();
+            defx!();
+        }
+        fn func2() {
+            deo!("unnamed");
+        }
+        let printed = thread::Builder::new()
+            .name(String::from("worker"))
+            .spawn(|| capture(func1))
+            .unwrap()
+            .join()
+            .unwrap();
+        let printed_unnamed = thread::spawn(|| capture(func2)).join().unwrap();
+        set_prefix(Prefix::ALL);
+        let printed_all = thread::spawn(|| capture(|| e!("all"))).join().unwrap();
+        assert_eq!(set_prefix(Prefix::NONE), Prefix::ALL);
+
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines.len(), 2, "{:?}", lines);
+        assert!(lines[0].ends_with(" worker   →func1: "), "{:?}", lines[0]);
+        assert!(lines[1].ends_with(" worker   ←func1: "), "{:?}", lines[1]);
+        let sequence = |line: &str| line[..6].trim_start().parse::<usize>().unwrap();
+        assert!(sequence(lines[0]) < sequence(lines[1]));
+        // sequence, thread, message
+        let fields: Vec<&str> = printed_unnamed.split_whitespace().collect();
+        assert!(sequence(&printed_unnamed) > sequence(lines[1]));
+        assert!(fields[1].starts_with('T'), "{:?}", fields);
+        assert_eq!(&fields[2..], ["unnamed"]);
+        // sequence, elapsed, timestamp, thread, message
+        let fields: Vec<&str> = printed_all.split_whitespace().collect();
+        assert_eq!(fields.len(), 5, "{:?}", fields);
+        assert!(fields[1].ends_with('s'), "{:?}", fields);
+        assert!(fields[2].ends_with('Z') && fields[2].contains('T'), "{:?}", fields);
+        assert!(fields[3].starts_with('T'), "{:?}", fields);
+        assert_eq!(fields[4], "all");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_elapsed_exiting() {
+        let _lock = test_settings_lock();
+        struct PrintOnDrop;
+        impl Drop for PrintOnDrop {
+            fn drop(&mut self) {
+                pfn!("test_set_elapsed_exiting");
+                pfx!("test_set_elapsed_exiting");
+            }
+        }
+        thread_local! {
+            static PRINT_ON_DROP: PrintOnDrop = const { PrintOnDrop };
+        }
+        fn func1() {
+            // thread-local destructors run in reverse order, so the entries
+            // are destroyed before `PRINT_ON_DROP` prints
+            PRINT_ON_DROP.with(|_| ());
+            pfn!("test_set_elapsed_exiting");
+        }
+        let buffer = Buffer::default();
+        set_writer(Box::new(buffer.clone()));
+        set_elapsed(true);
+        thread::spawn(func1).join().unwrap();
+        set_elapsed(false);
+        reset_writer();
+        // other tests may print to the buffer at the same time
+        let printed = buffer.string();
+        let lines: Vec<&str> = printed
+            .lines()
+            .filter(|line| line.ends_with("test_set_elapsed_exiting"))
+            .collect();
+        // the exit line has no elapsed time
+        assert_eq!(lines.len(), 3, "{:?}", printed);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    #[cfg_attr(not(debug_assertions), ignore = "stack offsets differ when functions are inlined")]
+    fn test_set_elapsed() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defn!();
+            thread::sleep(Duration::from_millis(2));
+            defx!("({})", 1);
+        }
+        fn func2() {
+            pfn!();
+            func1();
+            deñ!();
+            let _guard = defg!();
+            pfx!();
+        }
+        set_elapsed(true);
+        let printed = thread::spawn(|| capture(func2)).join().unwrap();
+        set_elapsed(false);
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines.len(), 7, "{:?}", lines);
+        assert_eq!(lines[0], "→func2: ");
+        assert_eq!(lines[1], "    →func1: ");
+        assert!(lines[2].starts_with("    ←func1: (1) ["), "{:?}", lines[2]);
+        assert!(lines[2].ends_with("ms]"), "{:?}", lines[2]);
+        assert_eq!(lines[3], "↔");
+        assert_eq!(lines[4], "→func2: ");
+        // `pfx!` is measured from `pfn!` and the guard from `defg!`
+        let micros = |line: &str| -> f64 {
+            let elapsed = line.strip_prefix("←func2: [").unwrap().strip_suffix(']').unwrap();
+            let at = elapsed.find(|c: char| c.is_alphabetic()).unwrap();
+            let number: f64 = elapsed[..at].parse().unwrap();
+            match &elapsed[at..] {
+                "s" => number * 1e6,
+                "ms" => number * 1e3,
+                "µs" => number,
+                "ns" => number / 1e3,
+                unit => panic!("unexpected unit {:?}", unit),
+            }
+        };
+        assert!(micros(lines[5]) >= 2000.0, "{:?}", lines[5]);
+        assert!(micros(lines[6]) < micros(lines[5]), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_filter_parse() {
+        assert_eq!(
+            Filter::parse(""),
+            Filter {
+                default: true,
+                directives: vec![]
+            }
+        );
+        assert_eq!(
+            Filter::parse(" OFF "),
+            Filter {
+                default: false,
+                directives: vec![]
+            }
+        );
+        assert_eq!(
+            Filter::parse("a::b=off, on ,a::b::c=ON,a=bad,a::b::c::d,=x,,"),
+            Filter {
+                default: true,
+                directives: vec![
+                    (String::from("a::b::c::d"), true),
+                    (String::from("a::b::c"), true),
+                    (String::from("a::b"), false),
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn test_filter_enabled() {
+        let filter = Filter::parse("mycrate::parser=off,mycrate::net::connect=on");
+        assert!(filter.enabled("mycrate::main"));
+        assert!(!filter.enabled("mycrate::parser"));
+        assert!(!filter.enabled("mycrate::parser::parse"));
+        assert!(filter.enabled("mycrate::parser2::parse"));
+        assert!(filter.enabled("mycrate::net::connect::retry"));
+        let filter = Filter::parse("0,mycrate::net=1,mycrate::net::connect=false");
+        assert!(!filter.enabled("mycrate::main"));
+        assert!(filter.enabled("mycrate::net::send"));
+        assert!(!filter.enabled("mycrate::net::connect"));
+    }
+
+    #[test]
+    fn test_filter_enabled_trait_impl() {
+        let filter = Filter::parse("mycrate::parser=off");
+        assert!(!filter.enabled("<mycrate::parser::Foo as core::fmt::Display>::fmt"));
+        assert!(!filter.enabled("<mycrate::parser::Foo<u8> as mycrate::Parse>::parse"));
+        assert!(filter.enabled("<mycrate::net::Foo as core::fmt::Display>::fmt"));
+        let filter = Filter::parse("off,mycrate::parser");
+        assert!(filter.enabled("<mycrate::parser::Foo as core::fmt::Display>::fmt"));
+        assert!(!filter.enabled("<core::fmt::Arguments as mycrate::parser::Parse>::parse"));
+    }
+
+    #[test]
+    fn test_filter_enabled_trait_impl_type() {
+        let filter = Filter::parse("mycrate::parser::Foo=off");
+        assert!(!filter.enabled("<mycrate::parser::Foo as core::fmt::Display>::fmt"));
+        assert!(!filter.enabled("<mycrate::parser::Foo<u8> as mycrate::Parse>::parse"));
+        assert!(!filter.enabled("mycrate::parser::Foo::new"));
+        assert!(!filter.enabled("mycrate::parser::Foo<_>::new"));
+        assert!(filter.enabled("<mycrate::parser::Foo2 as core::fmt::Display>::fmt"));
+        assert!(filter.enabled("mycrate::parser::Foo2::new"));
+        assert!(filter.enabled("mycrate::parser::Foo2<_>::new"));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_filter() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defo!("func1");
+        }
+        fn func2() {
+            defo!("func2");
+        }
+        let printed = thread::spawn(|| {
+            capture(|| {
+                set_filter("si_trace_print::printers::tests::test_set_filter::func1=off");
+                func1();
+                func2();
+                set_filter("off,si_trace_print::printers::tests::test_set_filter::func1");
+                func1();
+                func2();
+                set_filter("");
+            })
+        })
+        .join()
+        .unwrap();
+        assert_eq!(printed, " func2: func2\n func1: func1\n");
+    }
+
+    mod parser {
+        pub struct Foo(pub u8);
+
+        impl std::fmt::Display for Foo {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                crate::defo!("{}", self.0);
+                write!(f, "Foo({})", self.0)
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_filter_trait_impl() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            set_filter("si_trace_print::printers::tests::parser=off");
+            assert_eq!(parser::Foo(1).to_string(), "Foo(1)");
+            set_filter("off,si_trace_print::printers::tests::parser");
+            assert_eq!(parser::Foo(2).to_string(), "Foo(2)");
+            set_filter("");
+        }
+        let printed = thread::spawn(|| capture(func1)).join().unwrap();
+        // called from within `std`, so indented by an unknown amount
+        assert_eq!(printed.trim_start(), "fmt: 2\n");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_set_enabled() {
+        let _lock = test_settings_lock();
+        fn not_evaluated() -> usize {
+            panic!("argument evaluated while disabled");
+        }
+        let printed = thread::spawn(|| {
+            capture(|| {
+                let enabled = set_enabled(false);
+                pfn!("{}", not_evaluated());
+                deo!("{}", not_evaluated());
+                let _guard = efg!("{}", not_evaluated());
+                set_enabled(enabled);
+                e!("enabled");
+            })
+        })
+        .join()
+        .unwrap();
+        assert_eq!(printed, "enabled\n");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_capture_nested() {
+        let _lock = test_settings_lock();
+        let printed = thread::spawn(|| {
+            capture(|| {
+                e!("outer 1");
+                let inner = capture(|| {
+                    p!("inner");
+                });
+                assert_eq!(inner, "inner\n");
+                e!("outer 2");
+            })
+        })
+        .join()
+        .unwrap();
+        assert_eq!(printed, "outer 1\nouter 2\n");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    fn test_capture_panic() {
+        let _lock = test_settings_lock();
+        let printed = thread::spawn(|| {
+            let result = std::panic::catch_unwind(|| {
+                capture(|| {
+                    e!("lost");
+                    panic!("test_capture_panic");
+                })
+            });
+            assert!(result.is_err());
+            capture(|| e!("after panic"))
+        })
+        .join()
+        .unwrap();
+        assert_eq!(printed, "after panic\n");
+    }
 
     // `p`rintln tests
 
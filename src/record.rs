@@ -0,0 +1,217 @@
+// src/record.rs
+
+//! A [`Record`] of each macro print, passed to a hook function set by
+//! [`set_hook`].
+//!
+//! A hook allows consuming trace events programmatically instead of parsing
+//! the printed text.
+//!
+//! ```rust
+//! use si_trace_print::record::{set_hook, Kind, Record};
+//! use si_trace_print::{defn, defx};
+//!
+//! fn hook(record: &Record) {
+//!     if record.kind == Kind::Enter {
+//!         // send `record` to some other tool
+//!     }
+//! }
+//!
+//! fn main() {
+//!     set_hook(hook);
+//!     defn!("hello");
+//!     defx!("goodbye");
+//! }
+//! ```
+
+use std::fmt;
+use std::sync::RwLock;
+use std::thread::ThreadId;
+use std::time::Duration;
+
+extern crate lazy_static;
+use lazy_static::lazy_static;
+
+/// The kind of printing, i.e. which macro printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Printing without indentation, e.g. `p!`, `de!`.
+    Plain,
+    /// Printing within a function, e.g. `po!`, `defo!`.
+    /// Printed with [`so()`].
+    ///
+    /// [`so()`]: crate::stack::so
+    Offset,
+    /// Printing when entering a function, e.g. `pn!`, `defn!`.
+    /// Printed with [`sn()`].
+    ///
+    /// [`sn()`]: crate::stack::sn
+    Enter,
+    /// Printing when exiting a function, e.g. `px!`, `defx!`.
+    /// Printed with [`sx()`].
+    ///
+    /// [`sx()`]: crate::stack::sx
+    Exit,
+    /// Printing when entering and exiting a function, e.g. `pñ!`, `defñ!`.
+    /// Printed with [`sñ()`].
+    ///
+    /// [`sñ()`]: crate::stack::sñ
+    EnterExit,
+}
+
+impl Kind {
+    /// Return the lowercase name of this `Kind`, e.g. `"enter_exit"`.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Plain => "plain",
+            Kind::Offset => "offset",
+            Kind::Enter => "enter",
+            Kind::Exit => "exit",
+            Kind::EnterExit => "enter_exit",
+        }
+    }
+}
+
+/// Everything known about one macro print.
+///
+/// More fields may be added, so a `Record` cannot be constructed or
+/// exhaustively destructured outside this crate.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct Record<'a> {
+    /// The kind of printing.
+    pub kind: Kind,
+    /// Current stack offset, the indentation level.
+    pub stack_offset: usize,
+    /// The calling function name, from [`function_name!`].
+    ///
+    /// [`function_name!`]: crate::function_name::function_name
+    pub function_name: &'static str,
+    /// The calling function full path, from [`function_name_full!`].
+    ///
+    /// [`function_name_full!`]: crate::function_name::function_name_full
+    pub function_name_full: &'static str,
+    /// The calling thread.
+    pub thread_id: ThreadId,
+    /// A small number for the calling thread, assigned in order of first
+    /// macro print, starting from `0`. Printed as e.g. `T3`.
+    pub thread_number: usize,
+    /// The calling module path, from [`module_path!`].
+    pub module_path: &'static str,
+    /// The calling source file, from [`file!`].
+    pub file: &'static str,
+    /// The calling source line, from [`line!`].
+    pub line: u32,
+    /// The message passed to the macro.
+    pub message: fmt::Arguments<'a>,
+    /// For [`Kind::Exit`], the time elapsed since the matching
+    /// [`Kind::Enter`]. Only if [`set_elapsed`] is enabled.
+    ///
+    /// [`set_elapsed`]: crate::printers::set_elapsed
+    pub elapsed: Option<Duration>,
+}
+
+/// A hook function. See [`set_hook`].
+pub type Hook = fn(&Record);
+
+lazy_static! {
+    /// The hook set by [`set_hook`].
+    static ref HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+}
+
+/// Call `hook` for every macro print from any thread.
+///
+/// The `hook` is called before the macro prints and while the
+/// [`GLOBAL_LOCK_PRINTER`] is held, so it must not call `si_trace_print`
+/// macros.
+///
+/// Returns the previously set hook, if any.
+///
+/// [`GLOBAL_LOCK_PRINTER`]: struct@crate::printers::GLOBAL_LOCK_PRINTER
+pub fn set_hook(hook: Hook) -> Option<Hook> {
+    HOOK.write().unwrap().replace(hook)
+}
+
+/// Undo [`set_hook`].
+///
+/// Returns the previously set hook, if any.
+pub fn reset_hook() -> Option<Hook> {
+    HOOK.write().unwrap().take()
+}
+
+/// Call the hook set by [`set_hook`], if any.
+pub(crate) fn call_hook(record: &Record) {
+    let hook: Option<Hook> = *HOOK.read().unwrap();
+    if let Some(hook) = hook {
+        hook(record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reset_hook, set_hook, Kind, Record};
+    use crate::test_settings_lock;
+    use crate::{defn, defo, defñ, deo, dpx, e};
+    use std::sync::Mutex;
+
+    type Recorded = (Kind, usize, &'static str, String, &'static str, &'static str, u32);
+
+    lazy_static::lazy_static! {
+        static ref RECORDS: Mutex<Vec<Recorded>> = Mutex::new(Vec::new());
+    }
+
+    /// Save records from function `test_set_hook`.
+    fn hook(record: &Record) {
+        if !record.function_name_full.contains("test_set_hook") {
+            return;
+        }
+        RECORDS.lock().unwrap().push((
+            record.kind,
+            record.stack_offset,
+            record.function_name,
+            record.message.to_string(),
+            record.module_path,
+            record.file,
+            record.line,
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "off", ignore = "feature `off` prints nothing")]
+    #[cfg_attr(not(debug_assertions), ignore = "stack offsets differ when functions are inlined")]
+    fn test_set_hook() {
+        let _lock = test_settings_lock();
+        fn func1() {
+            defñ!("func1 {}", 1);
+        }
+        set_hook(hook);
+        let line = line!() + 1;
+        e!("e");
+        defn!("defn");
+        func1();
+        deo!("deo {}", 2);
+        dpx!();
+        assert!(reset_hook().is_some());
+        defo!("not recorded");
+
+        let records = RECORDS.lock().unwrap();
+        assert_eq!(records.len(), 5, "{:?}", records);
+        let so = records[0].1;
+        let m = "si_trace_print::record::tests";
+        let f = file!();
+        let fname = "test_set_hook";
+        assert_eq!(records[0], (Kind::Plain, so, fname, String::from("e"), m, f, line));
+        assert_eq!(
+            records[1],
+            (Kind::Enter, so, fname, String::from("defn"), m, f, line + 1)
+        );
+        assert_eq!(records[2].0, Kind::EnterExit);
+        assert_eq!(records[2].1, so + 1);
+        assert_eq!(records[2].2, "func1");
+        assert_eq!(records[2].3, "func1 1");
+        assert_eq!(
+            records[3],
+            (Kind::Offset, so, fname, String::from("deo 2"), m, f, line + 3)
+        );
+        assert_eq!(records[4], (Kind::Exit, so, fname, String::new(), m, f, line + 4));
+    }
+}
@@ -6,7 +6,8 @@
 //! Library users should use macros provided in [`printers`].
 //!
 //! Function `stack_offset_set` is to force the setting of the "original" stack
-//! depth for a thread.
+//! depth for a thread. Function `stack_offset_reset` forgets it. Function
+//! `anchor_here` temporarily sets it to the calling function.
 //!
 //! Functions `sn`, `so`, `sx`, and `sñ` return a `&str` to preprint before
 //! tracing messages. These functions are used by macros in `printers`.
@@ -27,6 +28,13 @@
 //! Lack of indentation may occur in a `--release` build or other optimized
 //! builds.
 //!
+//! Call [`set_depth_mode`] with [`DepthMode::Counter`] to avoid walking the
+//! stack. Indentation then reflects the nesting of enter and exit macros.
+//! Or call [`set_depth_mode`] with [`DepthMode::Crates`] to count only stack
+//! frames within given crates, or with [`DepthMode::Inlined`] to count
+//! inlined functions.
+//! Functions `sn`, `so`, `sx`, and `sñ` always walk the stack.
+//!
 //! Credit to [**@eggyal** for the Stackoverflow Answer] that inspired this
 //! crate.
 //!
@@ -34,22 +42,28 @@
 //! [_The Rust Performance Book_]: https://nnethercote.github.io/perf-book/inlining.html
 //! [**@eggyal** for the Stackoverflow Answer]: https://stackoverflow.com/a/69142325/471376
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::Write;
+use std::sync::RwLock;
 use std::thread;
 use std::thread::ThreadId;
+use std::time::{Duration, Instant};
 
 extern crate backtrace;
 
-extern crate const_format;
-use const_format::concatcp;
-
 extern crate lazy_static;
 use lazy_static::lazy_static;
 
 extern crate mut_static;
 
+use crate::color;
+use crate::record::Kind;
+
 /// A _stack depth_ counter. Also a _stack offset_ counter.
-type StackDepth = usize;
+pub(crate) type StackDepth = usize;
 
 /// Map a `ThreadId` to a `StackDepth`. The private global singleton
 /// `STACK_OFFSET_TABLE` is this type.
@@ -78,6 +92,9 @@ lazy_static! {
 /// `stack_depth` decrements it's own stack depth by implied result of
 /// attribute `#[inline(always)]`.
 ///
+/// With [`DepthMode::Inlined`], count the functions inlined within each
+/// stack frame.
+///
 /// Credit to [@eggyal for the Stackoverflow Answer] that inspired this crate.
 ///
 /// [`stack_offset`]: stack_offset
@@ -86,15 +103,26 @@ lazy_static! {
 /// [@eggyal for the Stackoverflow Answer]: https://stackoverflow.com/a/69142325/471376
 #[inline(always)]
 fn stack_depth() -> StackDepth {
+    let inlined: bool = depth_mode() == DepthMode::Inlined;
     let mut sd: StackDepth = 0;
-    backtrace::trace(|_| {
-        sd += 1;
+    backtrace::trace(|frame| {
+        sd += if inlined { frame_functions(frame) } else { 1 };
         true
     });
 
     sd
 }
 
+/// Return the count of functions within the stack `frame`, i.e. the
+/// function of the frame plus the functions inlined into it.
+/// At least `1`.
+fn frame_functions(frame: &backtrace::Frame) -> StackDepth {
+    let mut count: StackDepth = 0;
+    backtrace::resolve_frame(frame, |_| count += 1);
+
+    std::cmp::max(count, 1)
+}
+
 /// Make sure the global STACK_OFFSET_TABLE has been created.
 #[inline(never)]
 fn stack_offset_table_create() -> bool {
@@ -137,7 +165,7 @@ fn stack_offset_table_create() -> bool {
 ///
 /// [`stack_offset_set`]: stack_offset_set
 #[inline(never)]
-fn stack_offset() -> StackDepth {
+pub(crate) fn stack_offset() -> StackDepth {
     // call `stack_offset_set` which will both check the table exists
     // and has an offset entry for this thread. If an entry is not already
     // present than initialize with `1` correction, to correct this function
@@ -180,7 +208,8 @@ fn stack_offset() -> StackDepth {
 /// [`printers`].
 ///
 /// Only the first call to `stack_offset_set` within a thread is used.
-/// Subsequent calls are ignored.
+/// Subsequent calls are ignored, unless [`stack_offset_reset`] is called.
+/// See also [`anchor_here`].
 ///
 /// A positive value `correction` will move the printed output to the right.
 /// If the `correction` is too negative then it will print to the left-most
@@ -228,48 +257,685 @@ pub fn stack_offset_set(correction: Option<isize>) {
     }
 }
 
-const S_0: &str = "";
-const S_1: &str = "    ";
-const S_2: &str = "        ";
-const S_3: &str = "            ";
-const S_4: &str = "                ";
-const S_5: &str = "                    ";
-const S_6: &str = "                        ";
-const S_7: &str = "                            ";
-const S_8: &str = "                                ";
-const S_9: &str = "                                    ";
-const S_10: &str = "                                        ";
-const S_11: &str = "                                            ";
-const S_12: &str = "                                                ";
-const S_13: &str = "                                                    ";
-const S_14: &str = "                                                        ";
-const S_15: &str = "                                                            ";
-const S_16: &str = "                                                                ";
-const S_17: &str = "                                                                    ";
-const S_18: &str = "                                                                        ";
-const S_19: &str = "                                                                            ";
-#[rustfmt::skip]
-const S_20: &str = "                                                                                ";
-#[rustfmt::skip]
-const S_21: &str = "                                                                                    ";
-#[rustfmt::skip]
-const S_22: &str = "                                                                                        ";
-#[rustfmt::skip]
-const S_23: &str = "                                                                                            ";
-#[rustfmt::skip]
-const S_24: &str = "                                                                                                ";
-#[rustfmt::skip]
-const S_25: &str = "                                                                                                    ";
-#[rustfmt::skip]
-const S_26: &str = "                                                                                                        ";
-#[rustfmt::skip]
-const S_27: &str = "                                                                                                            ";
-#[rustfmt::skip]
-const S_28: &str = "                                                                                                                ";
-#[rustfmt::skip]
-const S_29: &str = "                                                                                                                    ";
-#[rustfmt::skip]
-const S__: &str = "                                                                                                                        ";
+/// How the stack offset of each macro print is found.
+/// See [`set_depth_mode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DepthMode {
+    /// Count the stack frames of the current thread with
+    /// [`backtrace::trace`]. The default.
+    ///
+    /// Indentation follows the actual call stack, including functions that
+    /// do not use macros. This is slow and depends on inlining.
+    ///
+    /// [`backtrace::trace`]: https://docs.rs/backtrace/0.3.66/backtrace/fn.trace.html
+    #[default]
+    Backtrace,
+    /// Count enter and exit macros in a thread-local depth counter.
+    ///
+    /// Enter macros, e.g. `defn!`, print at the counter depth and then
+    /// increment it. Exit macros, e.g. `defx!`, decrement the counter and then
+    /// print at the counter depth. Macros `*o!` print at the depth of the
+    /// enclosing enter macro and macros `*ñ!` print one deeper.
+    ///
+    /// Indentation reflects the nesting of enter and exit macros exactly and
+    /// costs almost nothing. Functions that return without an exit macro
+    /// leave the counter too deep; prefer the guard macros, e.g. `defg!`.
+    Counter,
+    /// Count only the stack frames of functions within the given crate or
+    /// module path prefixes, e.g. `DepthMode::Crates(&["my_crate"])`.
+    ///
+    /// Each frame is resolved to a symbol name with [`backtrace`], so this
+    /// is slower than [`DepthMode::Backtrace`]. But runtime, std and test
+    /// harness frames are not counted, so the indentation does not depend on
+    /// the thread or a [`stack_offset_set`] correction. The outermost
+    /// counted frame has stack offset `0`.
+    ///
+    /// Requires debug symbols. Frames without a symbol name are not counted.
+    /// Functions inlined within a frame are each counted when resolved from
+    /// debug info, so optimized builds with debug info, e.g. `--release`
+    /// builds with `debug = true`, indent the same as debug builds.
+    ///
+    /// [`backtrace`]: https://docs.rs/backtrace/0.3.66/backtrace/fn.resolve_frame.html
+    Crates(&'static [&'static str]),
+    /// Count the stack frames like [`DepthMode::Backtrace`] but expand each
+    /// frame into the functions inlined within it, resolved with
+    /// [`backtrace`] from debug info.
+    ///
+    /// Requires debug info. Inlined functions add to the indentation in
+    /// optimized builds with debug info, e.g. `--release` builds with
+    /// `debug = true`. Without debug info the inlined functions are not found
+    /// and this is the same as [`DepthMode::Backtrace`] but slower.
+    ///
+    /// Set this mode before the first macro print of each thread; the
+    /// "original" stack depth of a thread is counted in the mode of its
+    /// first print.
+    ///
+    /// [`backtrace`]: https://docs.rs/backtrace/0.3.66/backtrace/fn.resolve_frame.html
+    Inlined,
+}
+
+lazy_static! {
+    /// The current [`DepthMode`].
+    static ref DEPTH_MODE: RwLock<DepthMode> = RwLock::new(DepthMode::default());
+}
+
+/// Set how the stack offset of each macro print is found, for all threads.
+///
+/// Returns the previous mode.
+///
+/// ```rust
+/// use si_trace_print::stack::{set_depth_mode, DepthMode};
+/// use si_trace_print::{defn, defo, defx};
+///
+/// set_depth_mode(DepthMode::Counter);
+/// defn!("indentation does not walk the stack");
+/// defo!();
+/// defx!();
+/// ```
+pub fn set_depth_mode(mode: DepthMode) -> DepthMode {
+    std::mem::replace(&mut *DEPTH_MODE.write().unwrap(), mode)
+}
+
+/// Return the current [`DepthMode`].
+pub fn depth_mode() -> DepthMode {
+    *DEPTH_MODE.read().unwrap()
+}
+
+/// Symbol name prefix of the `si_trace_print` functions called by macros,
+/// i.e. `__print` and `__print_enter`.
+const PRINT_SYMBOL_PREFIX: &str = "si_trace_print::printers::__print";
+
+/// Does symbol `name` start with one of the module path `prefixes`?
+///
+/// A trait method symbol, e.g. `<my_crate::Foo as core::fmt::Display>::fmt`,
+/// matches the path of the implementing type.
+fn symbol_matches(name: &str, prefixes: &[&str]) -> bool {
+    let name: &str = name.strip_prefix('<').unwrap_or(name);
+    prefixes.iter().any(|prefix| match name.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    })
+}
+
+/// Return the [`DepthMode::Crates`] stack offset, the count of functions
+/// within `prefixes` that called `__print`, less one.
+///
+/// Functions inlined into a stack frame are counted like [`frame_functions`]
+/// so the stack offset is the same in optimized builds with debug info.
+#[inline(never)]
+pub(crate) fn crate_depth(prefixes: &[&str]) -> StackDepth {
+    let mut sd: StackDepth = 0;
+    let mut name = String::new();
+    backtrace::trace(|frame| {
+        let mut matched: StackDepth = 0;
+        let mut print = false;
+        backtrace::resolve_frame(frame, |symbol| {
+            if let Some(symbol_name) = symbol.name() {
+                name.clear();
+                // the alternate format omits the trailing hash
+                _ = write!(name, "{:#}", symbol_name);
+                if symbol_matches(&name, prefixes) {
+                    matched += 1;
+                }
+                print |= name.starts_with(PRINT_SYMBOL_PREFIX);
+            }
+        });
+        if print {
+            // forget frames within `si_trace_print`
+            sd = 0;
+        } else {
+            sd += matched;
+        }
+        true
+    });
+
+    sd.saturating_sub(1)
+}
+
+thread_local! {
+    /// The [`DepthMode::Counter`] depth of this thread.
+    static DEPTH_COUNTER: Cell<StackDepth> = const { Cell::new(0) };
+}
+
+/// Return the [`DepthMode::Counter`] stack offset for printing a `kind` of
+/// macro and update the counter.
+pub(crate) fn depth_counter(kind: Kind) -> StackDepth {
+    DEPTH_COUNTER.with(|counter| {
+        let depth: StackDepth = counter.get();
+        match kind {
+            Kind::Plain | Kind::EnterExit => depth,
+            Kind::Offset => depth.saturating_sub(1),
+            Kind::Enter => {
+                counter.set(depth + 1);
+                depth
+            }
+            Kind::Exit => {
+                counter.set(depth.saturating_sub(1));
+                depth.saturating_sub(1)
+            }
+        }
+    })
+}
+
+/// Forget the "original" stack depth of the current thread set by
+/// [`stack_offset_set`].
+///
+/// The next call to [`stack_offset_set`], or the next macro print, sets the
+/// "original" stack depth again.
+pub fn stack_offset_reset() {
+    if !stack_offset_table_create() {
+        return;
+    }
+    let tid: ThreadId = thread::current().id();
+    match STACK_OFFSET_TABLE.write() {
+        Ok(mut table) => {
+            table.remove(&tid);
+        }
+        Err(_err) => {}
+    }
+}
+
+/// Restores the previous "original" stack depth of a thread when dropped.
+/// Returned by [`anchor_here`].
+#[must_use = "the previous stack offset is restored when the anchor is dropped; bind it with `let _anchor = ...`"]
+pub struct StackAnchor {
+    tid: ThreadId,
+    /// The previous "original" stack depth, if any.
+    previous: Option<StackDepth>,
+}
+
+impl Drop for StackAnchor {
+    fn drop(&mut self) {
+        if let Ok(mut table) = STACK_OFFSET_TABLE.write() {
+            match self.previous {
+                Some(so) => table.insert(self.tid, so),
+                None => table.remove(&self.tid),
+            };
+        }
+    }
+}
+
+/// Set the "original" stack depth of the current thread to the calling
+/// function, so macros printing from the calling function print at the
+/// left-most column.
+/// The previous "original" stack depth is restored when the returned
+/// [`StackAnchor`] is dropped.
+///
+/// Useful for callbacks invoked from deep within a framework, where the
+/// "original" stack depth set by an earlier macro print would indent all
+/// printing far to the right, or not at all.
+///
+/// Only affects [`DepthMode::Backtrace`] and [`DepthMode::Inlined`].
+///
+/// ```rust
+/// use si_trace_print::stack::anchor_here;
+/// use si_trace_print::{defn, defx};
+///
+/// fn callback() {
+///     let _anchor = anchor_here();
+///     defn!("printed at the left-most column");
+///     defx!();
+/// }
+/// ```
+#[inline(never)]
+pub fn anchor_here() -> StackAnchor {
+    let tid: ThreadId = thread::current().id();
+    // this function `anchor_here` frame stands in for the `__print` frame
+    // counted by later macro prints within the calling function
+    let so: StackDepth = stack_depth();
+    let previous: Option<StackDepth> = if stack_offset_table_create() {
+        match STACK_OFFSET_TABLE.write() {
+            Ok(mut table) => table.insert(tid, so),
+            Err(_err) => None,
+        }
+    } else {
+        None
+    };
+
+    StackAnchor { tid, previous }
+}
+
+/// A function entry saved by [`entry_push`].
+struct Entry {
+    so: StackDepth,
+    /// The full function name of the enter line.
+    function: &'static str,
+    /// The file and line of the enter line.
+    location: (&'static str, u32),
+    /// Was the enter line printed by a guard macro, e.g. `defg!`?
+    guard: bool,
+    instant: Instant,
+}
+
+thread_local! {
+    /// This thread's function entries, most recent last. Used for
+    /// [`set_elapsed`].
+    ///
+    /// [`set_elapsed`]: crate::printers::set_elapsed
+    static ENTRY_INSTANTS: RefCell<Vec<Entry>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Save the time of entering `function` at stack offset `so` from the enter
+/// line at `location`.
+///
+/// Entries at a deeper or the same stack offset are discarded as they never
+/// exited, e.g. a function that prints only `defn!`. Except entries from other
+/// enter lines in the same `function`, e.g. a `pfn!` followed by a `defg!`.
+///
+/// Does nothing when printing from a later thread-local destructor.
+pub(crate) fn entry_push(so: StackDepth, function: &'static str, location: (&'static str, u32), guard: bool) {
+    _ = ENTRY_INSTANTS.try_with(|entries| {
+        let mut entries = entries.borrow_mut();
+        entries.retain(|entry| {
+            entry.so < so || (entry.so == so && entry.function == function && entry.location != location)
+        });
+        entries.push(Entry {
+            so,
+            function,
+            location,
+            guard,
+            instant: Instant::now(),
+        });
+    });
+}
+
+/// Return the time elapsed since entering `function` at stack offset `so`.
+///
+/// A guard exit line matches the entry from its own enter `location`.
+/// Other exit lines, e.g. `pfx!`, match the most recent entry that was not
+/// printed by a guard macro.
+/// Return `None` if there was no matching [`entry_push`], or when printing
+/// from a later thread-local destructor.
+pub(crate) fn entry_pop(
+    so: StackDepth,
+    function: &'static str,
+    location: (&'static str, u32),
+    guard: bool,
+) -> Option<Duration> {
+    ENTRY_INSTANTS
+        .try_with(|entries| {
+            let mut entries = entries.borrow_mut();
+            // discard deeper entries that never exited
+            while matches!(entries.last(), Some(entry) if entry.so > so) {
+                entries.pop();
+            }
+            let at: usize = entries.iter().rposition(|entry| {
+                entry.so == so
+                    && entry.function == function
+                    && entry.guard == guard
+                    && (!guard || entry.location == location)
+            })?;
+
+            Some(entries.remove(at).instant.elapsed())
+        })
+        .ok()
+        .flatten()
+}
+
+/// Default indentation for one stack offset, four spaces.
+pub const INDENT_UNIT: &str = "    ";
+
+/// The lead printed after the indentation for each [`Kind`] of macro.
+/// See [`set_leads`].
+///
+/// More fields may be added, so create `Leads` with [`Leads::new`] or from a
+/// preset, e.g. [`Leads::ASCII`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Leads {
+    /// Lead of [`Kind::Offset`] macros, e.g. `defo!`.
+    pub offset: Cow<'static, str>,
+    /// Lead of [`Kind::Enter`] macros, e.g. `defn!`.
+    pub enter: Cow<'static, str>,
+    /// Lead of [`Kind::Exit`] macros, e.g. `defx!`.
+    pub exit: Cow<'static, str>,
+    /// Lead of [`Kind::EnterExit`] macros, e.g. `defñ!`.
+    pub enter_exit: Cow<'static, str>,
+}
+
+impl Leads {
+    /// Leads ` `, `→`, `←`, `↔`. The default.
+    pub const ARROWS: Leads = Leads {
+        offset: Cow::Borrowed(SO_LEAD),
+        enter: Cow::Borrowed(SN_LEAD),
+        exit: Cow::Borrowed(SX_LEAD),
+        enter_exit: Cow::Borrowed(SÑ_LEAD),
+    };
+
+    /// ASCII leads ` `, `>`, `<`, `=`, for tools that mangle non-ASCII text.
+    pub const ASCII: Leads = Leads {
+        offset: Cow::Borrowed(" "),
+        enter: Cow::Borrowed(">"),
+        exit: Cow::Borrowed("<"),
+        enter_exit: Cow::Borrowed("="),
+    };
+
+    /// Create `Leads` of [`Kind::Offset`], [`Kind::Enter`], [`Kind::Exit`],
+    /// and [`Kind::EnterExit`] macros.
+    pub fn new(
+        offset: impl Into<Cow<'static, str>>,
+        enter: impl Into<Cow<'static, str>>,
+        exit: impl Into<Cow<'static, str>>,
+        enter_exit: impl Into<Cow<'static, str>>,
+    ) -> Leads {
+        Leads {
+            offset: offset.into(),
+            enter: enter.into(),
+            exit: exit.into(),
+            enter_exit: enter_exit.into(),
+        }
+    }
+
+    /// Return the lead for a `kind` of macro. [`Kind::Plain`] has no lead.
+    pub fn get(&self, kind: Kind) -> &str {
+        match kind {
+            Kind::Plain => "",
+            Kind::Offset => &self.offset,
+            Kind::Enter => &self.enter,
+            Kind::Exit => &self.exit,
+            Kind::EnterExit => &self.enter_exit,
+        }
+    }
+}
+
+impl Default for Leads {
+    fn default() -> Leads {
+        Leads::ARROWS
+    }
+}
+
+/// How indentation is printed. See [`set_style`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Style {
+    /// Indent with the [`set_indent_unit`] unit. The default.
+    ///
+    /// ```text
+    /// →func1: (3)
+    ///     →func2: (4)
+    ///      func2: doing stuff...
+    ///         ↔func3:
+    ///     ←func2: (4)
+    /// ←func1: (3)
+    /// ```
+    #[default]
+    Indent,
+    /// Indent with vertical guides so nested enter and exit printing reads
+    /// like a tree.
+    ///
+    /// ```text
+    /// ├─→func1: (3)
+    /// │   ├─→func2: (4)
+    /// │   │  func2: doing stuff...
+    /// │   │   ├─↔func3:
+    /// │   └─←func2: (4)
+    /// └─←func1: (3)
+    /// ```
+    ///
+    /// Each thread tracks which enter printing has not yet exited, i.e. which
+    /// guides are still open.
+    Tree,
+}
+
+/// Indentation settings. See [`indent_at`].
+struct Indents {
+    /// See [`set_style`].
+    style: Style,
+    /// Indentation for one stack offset. See [`set_indent_unit`].
+    unit: Cow<'static, str>,
+    /// See [`set_leads`].
+    leads: Leads,
+    /// Print a depth marker at stack offsets at or beyond this.
+    /// See [`set_depth_marker`].
+    marker: Option<StackDepth>,
+    /// Map a stack offset and kind of macro to the [`static_indent_at`]
+    /// string for the current settings. Cleared when the settings change.
+    statics: HashMap<(StackDepth, Kind), &'static str>,
+    /// Every [`static_indent_at`] string, so each is generated only once.
+    interned: HashSet<&'static str>,
+}
+
+lazy_static! {
+    /// See [`indent_at`].
+    static ref INDENTS: RwLock<Indents> = RwLock::new(Indents {
+        style: Style::Indent,
+        unit: Cow::Borrowed(INDENT_UNIT),
+        leads: Leads::ARROWS,
+        marker: None,
+        statics: HashMap::new(),
+        interned: HashSet::new(),
+    });
+}
+
+/// Set how indentation is printed by macros.
+/// The default is [`Style::Indent`].
+///
+/// Returns the previous style.
+///
+/// ```rust
+/// use si_trace_print::stack::{set_style, Style};
+///
+/// set_style(Style::Tree);
+/// ```
+pub fn set_style(style: Style) -> Style {
+    std::mem::replace(&mut INDENTS.write().unwrap().style, style)
+}
+
+/// Set the indentation for one stack offset, e.g. `"  "`, `"\t"`, or
+/// `".   "`. The default is [`INDENT_UNIT`], four spaces.
+///
+/// Returns the previous indentation unit.
+///
+/// ```rust
+/// use si_trace_print::stack::set_indent_unit;
+///
+/// set_indent_unit("  ");
+/// set_indent_unit(String::from("-").repeat(4));
+/// ```
+pub fn set_indent_unit(unit: impl Into<Cow<'static, str>>) -> Cow<'static, str> {
+    let mut indents = INDENTS.write().unwrap();
+    indents.statics.clear();
+    std::mem::replace(&mut indents.unit, unit.into())
+}
+
+/// Set the lead printed after the indentation for each kind of macro.
+/// The default is [`Leads::ARROWS`].
+///
+/// Returns the previous leads.
+///
+/// ```rust
+/// use si_trace_print::stack::{set_leads, Leads};
+///
+/// set_leads(Leads::ASCII);
+/// set_leads(Leads::new(" ", "+", "-", "="));
+///
+/// let mut leads = Leads::ARROWS;
+/// leads.enter = String::from("=>").into();
+/// set_leads(leads);
+/// ```
+pub fn set_leads(leads: Leads) -> Leads {
+    let mut indents = INDENTS.write().unwrap();
+    indents.statics.clear();
+    std::mem::replace(&mut indents.leads, leads)
+}
+
+/// Print a depth marker, e.g. `[42]`, before the lead of printing at stack
+/// offsets at or beyond `threshold`. Deep indentation is difficult to
+/// read; the marker shows the stack offset.
+/// Pass `None` to not print a depth marker, the default.
+///
+/// Returns the previous `threshold`.
+///
+/// ```rust
+/// use si_trace_print::stack::set_depth_marker;
+///
+/// set_depth_marker(Some(20));
+/// ```
+///
+/// would print deep recursion like
+///
+/// ```text
+///                                                                             →recurse: 19
+///                                                                                 [20]→recurse: 20
+/// ```
+pub fn set_depth_marker(threshold: Option<StackDepth>) -> Option<StackDepth> {
+    let mut indents = INDENTS.write().unwrap();
+    indents.statics.clear();
+    std::mem::replace(&mut indents.marker, threshold)
+}
+
+/// Write the indentation for the stack offset `so` followed by the lead for
+/// `kind`. If `colored` then the indentation is colored by stack offset and
+/// the depth marker and lead are colored by `kind`, and the color is not
+/// reset after the lead.
+///
+/// There is no limit to the stack offset.
+fn indent_at<W: Write>(out: &mut W, so: StackDepth, kind: Kind, colored: bool, indents: &Indents) -> fmt::Result {
+    for so_ in 0..so {
+        if colored {
+            out.write_str(color::cycle(so_))?;
+        }
+        out.write_str(&indents.unit)?;
+    }
+    if colored {
+        out.write_str(color::kind(kind))?;
+    }
+    if let Some(threshold) = indents.marker {
+        if so >= threshold {
+            write!(out, "[{}]", so)?;
+        }
+    }
+
+    out.write_str(indents.leads.get(kind))
+}
+
+/// Return the uncolored [`indent_at`] string as a `'static` `str` for
+/// [`so()`], [`sn()`], [`sx()`], [`sñ()`]. Macros do not use this.
+///
+/// Each distinct string is generated once and never freed, like a constant.
+/// Changing the indentation settings and changing them back generates
+/// nothing new.
+///
+/// [`so()`]: so
+/// [`sn()`]: sn
+/// [`sx()`]: sx
+/// [`sñ()`]: sñ
+fn static_indent_at(so: StackDepth, kind: Kind) -> &'static str {
+    if let Some(indent) = INDENTS.read().unwrap().statics.get(&(so, kind)) {
+        return indent;
+    }
+    let mut indents = INDENTS.write().unwrap();
+    let mut indent = String::new();
+    _ = indent_at(&mut indent, so, kind, false, &indents);
+    let indent: &'static str = match indents.interned.get(indent.as_str()) {
+        Some(indent) => indent,
+        None => {
+            let indent: &'static str = Box::leak(indent.into_boxed_str());
+            indents.interned.insert(indent);
+            indent
+        }
+    };
+    indents.statics.insert((so, kind), indent);
+
+    indent
+}
+
+thread_local! {
+    /// For [`Style::Tree`], is the enter printing at each stack offset of
+    /// this thread not yet exited?
+    static TREE_SCOPES: RefCell<Vec<bool>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Return the [`Style::Tree`] indentation for the stack offset `so` followed
+/// by the lead for `kind`, and update the open scopes of this thread.
+/// `colored` is the same as [`indent_at`].
+///
+/// Return `None` if the open scopes of this thread were destroyed, i.e. when
+/// printing from a later thread-local destructor.
+fn tree_at(so: StackDepth, kind: Kind, colored: bool, indents: &Indents) -> Option<String> {
+    if kind == Kind::Plain {
+        return Some(String::new());
+    }
+    let width: usize = std::cmp::max(indents.unit.chars().count(), 1);
+    // the color of a column, or `""`
+    let column = |so_: StackDepth| if colored { color::cycle(so_) } else { "" };
+    TREE_SCOPES
+        .try_with(|scopes| {
+            let mut scopes = scopes.borrow_mut();
+            // deeper scopes have returned, whether or not they printed an exit
+            scopes.resize(so + 1, false);
+            let mut tree = String::new();
+            for (so_, open) in scopes[..so].iter().enumerate() {
+                if *open {
+                    tree.push_str(column(so_));
+                    tree.push('│');
+                } else {
+                    tree.push(' ');
+                }
+                tree.extend(std::iter::repeat(' ').take(width - 1));
+            }
+            if let Some(threshold) = indents.marker {
+                if so >= threshold {
+                    _ = write!(tree, "[{}]", so);
+                }
+            }
+            let connector: &str = match kind {
+                Kind::Plain => "",
+                Kind::Offset if scopes[so] => "│ ",
+                Kind::Offset => "  ",
+                Kind::Enter => {
+                    scopes[so] = true;
+                    "├─"
+                }
+                Kind::Exit => {
+                    scopes[so] = false;
+                    "└─"
+                }
+                Kind::EnterExit => "├─",
+            };
+            tree.push_str(column(so));
+            tree.push_str(connector);
+            if colored {
+                tree.push_str(color::kind(kind));
+            }
+            tree.push_str(indents.leads.get(kind));
+
+            tree
+        })
+        .ok()
+}
+
+/// Display the indentation for printing a macro. See [`lead_at`].
+pub(crate) enum Lead {
+    /// [`Style::Indent`] indentation, written by [`indent_at`] when displayed.
+    Indent { so: StackDepth, kind: Kind, colored: bool },
+    /// [`Style::Tree`] indentation.
+    Tree(String),
+}
+
+impl fmt::Display for Lead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lead::Indent { kind: Kind::Plain, .. } => Ok(()),
+            Lead::Indent { so, kind, colored } => indent_at(f, *so, *kind, *colored, &INDENTS.read().unwrap()),
+            Lead::Tree(tree) => f.write_str(tree),
+        }
+    }
+}
+
+/// Return the indentation for printing a `kind` of macro at the
+/// stack offset `so`, in the current [`Style`].
+/// `colored` is the same as [`indent_at`].
+pub(crate) fn lead_at(so: StackDepth, kind: Kind, colored: bool) -> Lead {
+    let indents = INDENTS.read().unwrap();
+    match indents.style {
+        Style::Tree => match tree_at(so, kind, colored, &indents) {
+            Some(tree) => Lead::Tree(tree),
+            // the thread is exiting
+            None => Lead::Indent { so, kind, colored },
+        },
+        Style::Indent => Lead::Indent { so, kind, colored },
+    }
+}
 
 /// Leading character for [`so()`]
 ///
@@ -277,42 +943,16 @@ const S__: &str = "
 pub(crate) const SO_LEAD: &str = " ";
 
 /// Return a string of **s**paces that is a multiple of the current
-/// stack **o**ffset with one trailing space.
+/// stack **o**ffset with one trailing space, or the [`Leads::offset`].
 pub fn so() -> &'static str {
-    let so_ = stack_offset();
-    match so_ {
-        0 => concatcp!(S_0, SO_LEAD),
-        1 => concatcp!(S_1, SO_LEAD),
-        2 => concatcp!(S_2, SO_LEAD),
-        3 => concatcp!(S_3, SO_LEAD),
-        4 => concatcp!(S_4, SO_LEAD),
-        5 => concatcp!(S_5, SO_LEAD),
-        6 => concatcp!(S_6, SO_LEAD),
-        7 => concatcp!(S_7, SO_LEAD),
-        8 => concatcp!(S_8, SO_LEAD),
-        9 => concatcp!(S_9, SO_LEAD),
-        10 => concatcp!(S_10, SO_LEAD),
-        11 => concatcp!(S_11, SO_LEAD),
-        12 => concatcp!(S_12, SO_LEAD),
-        13 => concatcp!(S_13, SO_LEAD),
-        14 => concatcp!(S_14, SO_LEAD),
-        15 => concatcp!(S_15, SO_LEAD),
-        16 => concatcp!(S_16, SO_LEAD),
-        17 => concatcp!(S_17, SO_LEAD),
-        18 => concatcp!(S_18, SO_LEAD),
-        19 => concatcp!(S_19, SO_LEAD),
-        20 => concatcp!(S_20, SO_LEAD),
-        21 => concatcp!(S_21, SO_LEAD),
-        22 => concatcp!(S_22, SO_LEAD),
-        23 => concatcp!(S_23, SO_LEAD),
-        24 => concatcp!(S_24, SO_LEAD),
-        25 => concatcp!(S_25, SO_LEAD),
-        26 => concatcp!(S_26, SO_LEAD),
-        27 => concatcp!(S_27, SO_LEAD),
-        28 => concatcp!(S_28, SO_LEAD),
-        29 => concatcp!(S_29, SO_LEAD),
-        _ => concatcp!(S__, SO_LEAD),
-    }
+    so_at(stack_offset())
+}
+
+/// [`so()`] for the stack offset `so`.
+///
+/// [`so()`]: so
+pub(crate) fn so_at(so: StackDepth) -> &'static str {
+    static_indent_at(so, Kind::Offset)
 }
 
 /// Leading character for [`sn()`]
@@ -321,44 +961,17 @@ pub fn so() -> &'static str {
 pub(crate) const SN_LEAD: &str = "→";
 
 /// Return a string of **s**paces that is a multiple of the current
-/// stack offset with trailing `→` signifying e**n**tering a function.
-///
-/// [`stack_offset()`]: stack_offset
+/// stack offset with trailing `→` signifying e**n**tering a function, or the
+/// [`Leads::enter`].
 pub fn sn() -> &'static str {
-    let so = stack_offset();
-    match so {
-        0 => concatcp!(S_0, SN_LEAD),
-        1 => concatcp!(S_1, SN_LEAD),
-        2 => concatcp!(S_2, SN_LEAD),
-        3 => concatcp!(S_3, SN_LEAD),
-        4 => concatcp!(S_4, SN_LEAD),
-        5 => concatcp!(S_5, SN_LEAD),
-        6 => concatcp!(S_6, SN_LEAD),
-        7 => concatcp!(S_7, SN_LEAD),
-        8 => concatcp!(S_8, SN_LEAD),
-        9 => concatcp!(S_9, SN_LEAD),
-        10 => concatcp!(S_10, SN_LEAD),
-        11 => concatcp!(S_11, SN_LEAD),
-        12 => concatcp!(S_12, SN_LEAD),
-        13 => concatcp!(S_13, SN_LEAD),
-        14 => concatcp!(S_14, SN_LEAD),
-        15 => concatcp!(S_15, SN_LEAD),
-        16 => concatcp!(S_16, SN_LEAD),
-        17 => concatcp!(S_17, SN_LEAD),
-        18 => concatcp!(S_18, SN_LEAD),
-        19 => concatcp!(S_19, SN_LEAD),
-        20 => concatcp!(S_20, SN_LEAD),
-        21 => concatcp!(S_21, SN_LEAD),
-        22 => concatcp!(S_22, SN_LEAD),
-        23 => concatcp!(S_23, SN_LEAD),
-        24 => concatcp!(S_24, SN_LEAD),
-        25 => concatcp!(S_25, SN_LEAD),
-        26 => concatcp!(S_26, SN_LEAD),
-        27 => concatcp!(S_27, SN_LEAD),
-        28 => concatcp!(S_28, SN_LEAD),
-        29 => concatcp!(S_29, SN_LEAD),
-        _ => concatcp!(S__, SN_LEAD),
-    }
+    sn_at(stack_offset())
+}
+
+/// [`sn()`] for the stack offset `so`.
+///
+/// [`sn()`]: sn
+pub(crate) fn sn_at(so: StackDepth) -> &'static str {
+    static_indent_at(so, Kind::Enter)
 }
 
 /// Leading character for [`sx()`]
@@ -367,44 +980,17 @@ pub fn sn() -> &'static str {
 pub(crate) const SX_LEAD: &str = "←";
 
 /// Return a string of **s**paces that is a multiple of the current
-/// stack offset with trailing `←` signifying e**x**iting a function.
-///
-/// [`stack_offset()`]: stack_offset
+/// stack offset with trailing `←` signifying e**x**iting a function, or the
+/// [`Leads::exit`].
 pub fn sx() -> &'static str {
-    let so = stack_offset();
-    match so {
-        0 => concatcp!(S_0, SX_LEAD),
-        1 => concatcp!(S_1, SX_LEAD),
-        2 => concatcp!(S_2, SX_LEAD),
-        3 => concatcp!(S_3, SX_LEAD),
-        4 => concatcp!(S_4, SX_LEAD),
-        5 => concatcp!(S_5, SX_LEAD),
-        6 => concatcp!(S_6, SX_LEAD),
-        7 => concatcp!(S_7, SX_LEAD),
-        8 => concatcp!(S_8, SX_LEAD),
-        9 => concatcp!(S_9, SX_LEAD),
-        10 => concatcp!(S_10, SX_LEAD),
-        11 => concatcp!(S_11, SX_LEAD),
-        12 => concatcp!(S_12, SX_LEAD),
-        13 => concatcp!(S_13, SX_LEAD),
-        14 => concatcp!(S_14, SX_LEAD),
-        15 => concatcp!(S_15, SX_LEAD),
-        16 => concatcp!(S_16, SX_LEAD),
-        17 => concatcp!(S_17, SX_LEAD),
-        18 => concatcp!(S_18, SX_LEAD),
-        19 => concatcp!(S_19, SX_LEAD),
-        20 => concatcp!(S_20, SX_LEAD),
-        21 => concatcp!(S_21, SX_LEAD),
-        22 => concatcp!(S_22, SX_LEAD),
-        23 => concatcp!(S_23, SX_LEAD),
-        24 => concatcp!(S_24, SX_LEAD),
-        25 => concatcp!(S_25, SX_LEAD),
-        26 => concatcp!(S_26, SX_LEAD),
-        27 => concatcp!(S_27, SX_LEAD),
-        28 => concatcp!(S_28, SX_LEAD),
-        29 => concatcp!(S_29, SX_LEAD),
-        _ => concatcp!(S__, SX_LEAD),
-    }
+    sx_at(stack_offset())
+}
+
+/// [`sx()`] for the stack offset `so`.
+///
+/// [`sx()`]: sx
+pub(crate) fn sx_at(so: StackDepth) -> &'static str {
+    static_indent_at(so, Kind::Exit)
 }
 
 /// Leading character for [`sñ()`]
@@ -414,49 +1000,27 @@ pub(crate) const SÑ_LEAD: &str = "↔";
 
 /// Return a string of **s**paces that is a multiple of the current
 /// stack_offset with trailing `↔` signifying e**n**tering and e**x**iting
-/// a function.
-///
-/// [`stack_offset()`]: stack_offset
+/// a function, or the [`Leads::enter_exit`].
 pub fn sñ() -> &'static str {
-    let so = stack_offset();
-    match so {
-        0 => concatcp!(S_0, SÑ_LEAD),
-        1 => concatcp!(S_1, SÑ_LEAD),
-        2 => concatcp!(S_2, SÑ_LEAD),
-        3 => concatcp!(S_3, SÑ_LEAD),
-        4 => concatcp!(S_4, SÑ_LEAD),
-        5 => concatcp!(S_5, SÑ_LEAD),
-        6 => concatcp!(S_6, SÑ_LEAD),
-        7 => concatcp!(S_7, SÑ_LEAD),
-        8 => concatcp!(S_8, SÑ_LEAD),
-        9 => concatcp!(S_9, SÑ_LEAD),
-        10 => concatcp!(S_10, SÑ_LEAD),
-        11 => concatcp!(S_11, SÑ_LEAD),
-        12 => concatcp!(S_12, SÑ_LEAD),
-        13 => concatcp!(S_13, SÑ_LEAD),
-        14 => concatcp!(S_14, SÑ_LEAD),
-        15 => concatcp!(S_15, SÑ_LEAD),
-        16 => concatcp!(S_16, SÑ_LEAD),
-        17 => concatcp!(S_17, SÑ_LEAD),
-        18 => concatcp!(S_18, SÑ_LEAD),
-        19 => concatcp!(S_19, SÑ_LEAD),
-        20 => concatcp!(S_10, SÑ_LEAD),
-        21 => concatcp!(S_21, SÑ_LEAD),
-        22 => concatcp!(S_22, SÑ_LEAD),
-        23 => concatcp!(S_23, SÑ_LEAD),
-        24 => concatcp!(S_24, SÑ_LEAD),
-        25 => concatcp!(S_25, SÑ_LEAD),
-        26 => concatcp!(S_26, SÑ_LEAD),
-        27 => concatcp!(S_27, SÑ_LEAD),
-        28 => concatcp!(S_28, SÑ_LEAD),
-        29 => concatcp!(S_29, SÑ_LEAD),
-        _ => concatcp!(S__, SÑ_LEAD),
-    }
+    sñ_at(stack_offset())
+}
+
+/// [`sñ()`] for the stack offset `so`.
+///
+/// [`sñ()`]: sñ
+pub(crate) fn sñ_at(so: StackDepth) -> &'static str {
+    static_indent_at(so, Kind::EnterExit)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{sn, so, stack_depth, stack_offset, stack_offset_set, sx, sñ, StackDepth};
+    use super::{
+        anchor_here, depth_counter, entry_pop, entry_push, set_depth_marker, set_indent_unit, set_leads, sn, sn_at, so,
+        so_at, stack_depth, stack_offset, stack_offset_reset, stack_offset_set, sx, sx_at, symbol_matches, sñ, sñ_at,
+        Leads, StackDepth, ENTRY_INSTANTS, INDENTS, INDENT_UNIT,
+    };
+    use crate::record::Kind;
+    use crate::test_settings_lock;
 
     #[test]
     fn test_stack_depth() {
@@ -594,6 +1158,210 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_stack_offset_reset() {
+        std::thread::spawn(|| {
+            stack_offset_set(Some(5));
+            let a = stack_offset();
+            // attempt to set again
+            stack_offset_set(None);
+            assert_eq!(stack_offset(), a);
+            stack_offset_reset();
+            stack_offset_set(None);
+            let b = stack_offset();
+            assert_eq!(a, b + 5);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(debug_assertions), ignore = "stack offsets differ when functions are inlined")]
+    fn test_anchor_here() {
+        let _lock = test_settings_lock();
+        std::thread::spawn(|| {
+            stack_offset_set(Some(5));
+            let a = so();
+            fn func1() -> &'static str {
+                so()
+            }
+            fn func2() -> (&'static str, &'static str) {
+                let _anchor = anchor_here();
+                (so(), func1())
+            }
+            let (b, c) = func2();
+            assert_eq!(b, " ");
+            assert_eq!(c, "     ");
+            // previous stack offset is restored
+            assert_eq!(so(), a);
+        })
+        .join()
+        .unwrap();
+        // without a previous stack offset
+        std::thread::spawn(|| {
+            let anchor = anchor_here();
+            assert_eq!(so(), " ");
+            drop(anchor);
+            // not ignored
+            stack_offset_set(Some(5));
+            assert_eq!(stack_offset(), 5);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_entry_push_pop() {
+        const F: &str = "f";
+        const L: (&str, u32) = ("file.rs", 1);
+        entry_push(1, F, L, false);
+        entry_push(2, F, L, false);
+        entry_push(3, F, L, false);
+        // stack offset 3 never exited
+        assert!(entry_pop(2, F, L, false).is_some());
+        assert!(entry_pop(2, F, L, false).is_none());
+        assert!(entry_pop(1, F, L, false).is_some());
+        assert!(entry_pop(1, F, L, false).is_none());
+        entry_push(1, F, L, false);
+        // stack offset 1 never exited
+        assert!(entry_pop(0, F, L, false).is_none());
+        assert!(entry_pop(1, F, L, false).is_none());
+    }
+
+    #[test]
+    fn test_entry_push_pop_guard() {
+        const F: &str = "f";
+        entry_push(1, F, ("file.rs", 1), false);
+        entry_push(1, F, ("file.rs", 2), true);
+        // a plain exit line does not match the guard entry
+        assert!(entry_pop(1, "g", ("file.rs", 3), false).is_none());
+        assert!(entry_pop(1, F, ("file.rs", 3), false).is_some());
+        assert!(entry_pop(1, F, ("file.rs", 3), false).is_none());
+        assert!(entry_pop(1, F, ("file.rs", 1), true).is_none());
+        assert!(entry_pop(1, F, ("file.rs", 2), true).is_some());
+        assert!(entry_pop(1, F, ("file.rs", 2), true).is_none());
+    }
+
+    #[test]
+    fn test_entry_push_never_exited() {
+        fn entries() -> usize {
+            ENTRY_INSTANTS.with(|entries| entries.borrow().len())
+        }
+        let before = entries();
+        entry_push(1, "f", ("file.rs", 1), false);
+        for _ in 0..10 {
+            // a function at stack offset 2 that prints only an enter line
+            entry_push(2, "g", ("file.rs", 2), false);
+        }
+        assert_eq!(entries(), before + 2);
+        // another function at stack offset 2
+        entry_push(2, "h", ("file.rs", 3), false);
+        assert_eq!(entries(), before + 2);
+        entry_push(1, "f", ("file.rs", 1), false);
+        assert_eq!(entries(), before + 1);
+        assert!(entry_pop(1, "f", ("file.rs", 4), false).is_some());
+        assert_eq!(entries(), before);
+    }
+
+    #[test]
+    fn test_depth_counter() {
+        std::thread::spawn(|| {
+            assert_eq!(depth_counter(Kind::Offset), 0);
+            assert_eq!(depth_counter(Kind::Enter), 0);
+            assert_eq!(depth_counter(Kind::Offset), 0);
+            assert_eq!(depth_counter(Kind::EnterExit), 1);
+            assert_eq!(depth_counter(Kind::Enter), 1);
+            assert_eq!(depth_counter(Kind::Offset), 1);
+            assert_eq!(depth_counter(Kind::Plain), 2);
+            assert_eq!(depth_counter(Kind::Exit), 1);
+            assert_eq!(depth_counter(Kind::Exit), 0);
+            // too many exits
+            assert_eq!(depth_counter(Kind::Exit), 0);
+            assert_eq!(depth_counter(Kind::Enter), 0);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_symbol_matches() {
+        let prefixes = &["my_crate", "other::module"];
+        assert!(symbol_matches("my_crate::func1", prefixes));
+        assert!(symbol_matches("my_crate::func1::{{closure}}", prefixes));
+        assert!(symbol_matches("<my_crate::Foo as core::fmt::Display>::fmt", prefixes));
+        assert!(symbol_matches("other::module::func2", prefixes));
+        assert!(symbol_matches("my_crate", prefixes));
+        assert!(!symbol_matches("my_crate_two::func1", prefixes));
+        assert!(!symbol_matches("other::module2::func2", prefixes));
+        assert!(!symbol_matches("std::rt::lang_start", prefixes));
+        assert!(!symbol_matches(
+            "<alloc::vec::Vec<my_crate::Foo> as core::ops::drop::Drop>::drop",
+            prefixes
+        ));
+    }
+
+    #[test]
+    fn test_indent_at() {
+        let _lock = test_settings_lock();
+        assert_eq!(so_at(0), " ");
+        assert_eq!(sn_at(1), "    →");
+        assert_eq!(sx_at(2), "        ←");
+        assert_eq!(sñ_at(20), format!("{}↔", " ".repeat(80)));
+        // no limit to the indentation
+        assert_eq!(sn_at(29).len() + 4, sn_at(30).len());
+        assert_eq!(sn_at(1000), format!("{}→", " ".repeat(4000)));
+        // generated once
+        assert!(std::ptr::eq(sn_at(1000), sn_at(1000)));
+    }
+
+    #[test]
+    fn test_set_depth_marker() {
+        let _lock = test_settings_lock();
+        let sn3: &'static str = sn_at(3);
+        assert_eq!(set_depth_marker(Some(3)), None);
+        assert_eq!(sn_at(2), "        →");
+        assert_eq!(sn_at(3), "            [3]→");
+        assert_eq!(sx_at(42), format!("{}[42]←", " ".repeat(168)));
+        assert_eq!(set_depth_marker(None), Some(3));
+        assert_eq!(sn_at(3), "            →");
+        // the same string is not generated again
+        assert!(std::ptr::eq(sn_at(3), sn3));
+    }
+
+    #[test]
+    fn test_set_indent_unit() {
+        let _lock = test_settings_lock();
+        assert_eq!(set_indent_unit("\t"), INDENT_UNIT);
+        assert_eq!(sn_at(2), "\t\t→");
+        set_indent_unit(String::from(".   "));
+        assert_eq!(so_at(3), ".   .   .    ");
+        assert_eq!(set_indent_unit(INDENT_UNIT), ".   ");
+        assert_eq!(sn_at(2), "        →");
+        // changing the unit back and forth generates no new strings
+        let interned = || INDENTS.read().unwrap().interned.len();
+        let before = interned();
+        for unit in ["\t", INDENT_UNIT, "\t", INDENT_UNIT] {
+            set_indent_unit(unit);
+            sn_at(2);
+        }
+        assert_eq!(interned(), before);
+    }
+
+    #[test]
+    fn test_set_leads() {
+        let _lock = test_settings_lock();
+        assert_eq!(set_leads(Leads::ASCII), Leads::ARROWS);
+        assert_eq!(so_at(1), "     ");
+        assert_eq!(sn_at(1), "    >");
+        assert_eq!(sx_at(1), "    <");
+        assert_eq!(sñ_at(1), "    =");
+        set_leads(Leads::new(" ", String::from("+ "), "<", "="));
+        assert_eq!(sn_at(0), "+ ");
+        assert_eq!(sx_at(0), "<");
+        set_leads(Leads::default());
+        assert_eq!(sn_at(1), "    →");
+    }
+
     #[test]
     fn test_so() {
         so();
@@ -13,13 +13,15 @@ cd "$(dirname -- "${0}")/.."
 set -x
 
 cargo msrv verify
+cargo msrv verify --path si_trace_print_macros
 cargo build --lib --verbose
 cargo build --lib --verbose --release
 cargo test --verbose --all-features --future-incompat-report
-cargo check --all-targets --release
-cargo clippy --no-deps --all-targets --all-features
+cargo test --verbose --package si_trace_print_macros
+cargo check --workspace --all-targets --release
+cargo clippy --no-deps --workspace --all-targets --all-features
 cargo doc --locked --release --frozen --no-deps
 cargo publish --dry-run --allow-dirty
 cargo fmt --verbose --check
-cargo doc --locked --release --frozen --no-deps -v
-cargo clippy --no-deps --verbose --all-targets --all-features -- -D warnings
+cargo doc --locked --release --frozen --no-deps --workspace -v
+cargo clippy --no-deps --verbose --workspace --all-targets --all-features -- -D warnings